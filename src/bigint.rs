@@ -0,0 +1,433 @@
+use std::{
+  cmp::Ordering,
+  fmt,
+  ops::{Add, Mul, Neg, Sub},
+};
+
+/// An arbitrary-precision integer, stored as a sign and a little-endian
+/// magnitude in base 2^32 with no trailing zero limbs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigInt {
+  negative: bool,
+  magnitude: Vec<u32>,
+}
+
+impl BigInt {
+  fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+    while magnitude.last() == Some(&0) {
+      magnitude.pop();
+    }
+    let negative = negative && !magnitude.is_empty();
+    Self {
+      negative,
+      magnitude,
+    }
+  }
+
+  /// Parses unsigned digits in `radix`, without prefix or separators.
+  pub fn parse_radix(digits: &str, radix: u32) -> Option<Self> {
+    if digits.is_empty() {
+      return None;
+    }
+    let mut magnitude = Vec::new();
+    for c in digits.chars() {
+      let digit = c.to_digit(radix)?;
+      mul_small_add(&mut magnitude, radix, digit);
+    }
+    Some(Self::new(false, magnitude))
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.magnitude.is_empty()
+  }
+
+  pub fn is_negative(&self) -> bool {
+    self.negative
+  }
+
+  /// The number of bits in the magnitude.
+  pub fn bits(&self) -> u64 {
+    self.magnitude.last().map_or(0, |&top| {
+      self.magnitude.len() as u64 * 32 - top.leading_zeros() as u64
+    })
+  }
+
+  pub fn abs(&self) -> Self {
+    Self::new(false, self.magnitude.clone())
+  }
+
+  pub fn to_i64(&self) -> Option<i64> {
+    if self.magnitude.len() > 2 {
+      return None;
+    }
+    let magnitude = self
+      .magnitude
+      .iter()
+      .rev()
+      .fold(0u64, |acc, &limb| acc << 32 | limb as u64);
+    if self.negative {
+      0i64.checked_sub_unsigned(magnitude)
+    } else {
+      i64::try_from(magnitude).ok()
+    }
+  }
+
+  /// The nearest float, or an infinity when out of range.
+  pub fn to_f64(&self) -> f64 {
+    self.to_string().parse().unwrap_or(f64::NAN)
+  }
+
+  /// Truncating division, so the remainder takes the sign of `self`.
+  /// Returns `None` when dividing by zero.
+  pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+    if other.is_zero() {
+      return None;
+    }
+    let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &other.magnitude);
+    Some((
+      Self::new(self.negative != other.negative, quotient),
+      Self::new(self.negative, remainder),
+    ))
+  }
+
+  /// Division rounding towards negative infinity, with the remainder
+  /// taking the sign of `other`.
+  pub fn div_mod_floor(&self, other: &Self) -> Option<(Self, Self)> {
+    let (quotient, remainder) = self.div_rem(other)?;
+    if !remainder.is_zero() && remainder.negative != other.negative {
+      Some((&quotient - &Self::from(1), &remainder + other))
+    } else {
+      Some((quotient, remainder))
+    }
+  }
+
+  /// The greatest common divisor, always non-negative.
+  pub fn gcd(&self, other: &Self) -> Self {
+    let (mut a, mut b) = (self.abs(), other.abs());
+    while let Some((_, remainder)) = a.div_rem(&b) {
+      (a, b) = (b, remainder);
+    }
+    a
+  }
+
+  pub fn pow(&self, mut exponent: u32) -> Self {
+    let mut base = self.clone();
+    let mut result = Self::from(1);
+    while exponent > 0 {
+      if exponent & 1 == 1 {
+        result = &result * &base;
+      }
+      exponent >>= 1;
+      if exponent > 0 {
+        base = &base * &base;
+      }
+    }
+    result
+  }
+}
+
+impl From<i64> for BigInt {
+  fn from(n: i64) -> Self {
+    let magnitude = n.unsigned_abs();
+    Self::new(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+  }
+}
+
+impl Ord for BigInt {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self.negative, other.negative) {
+      (false, true) => Ordering::Greater,
+      (true, false) => Ordering::Less,
+      (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+      (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+    }
+  }
+}
+
+impl PartialOrd for BigInt {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Neg for &BigInt {
+  type Output = BigInt;
+
+  fn neg(self) -> BigInt {
+    BigInt::new(!self.negative, self.magnitude.clone())
+  }
+}
+
+impl Add for &BigInt {
+  type Output = BigInt;
+
+  fn add(self, other: &BigInt) -> BigInt {
+    if self.negative == other.negative {
+      return BigInt::new(
+        self.negative,
+        add_magnitude(&self.magnitude, &other.magnitude),
+      );
+    }
+    match cmp_magnitude(&self.magnitude, &other.magnitude) {
+      Ordering::Less => BigInt::new(
+        other.negative,
+        sub_magnitude(&other.magnitude, &self.magnitude),
+      ),
+      _ => BigInt::new(
+        self.negative,
+        sub_magnitude(&self.magnitude, &other.magnitude),
+      ),
+    }
+  }
+}
+
+impl Sub for &BigInt {
+  type Output = BigInt;
+
+  fn sub(self, other: &BigInt) -> BigInt {
+    self + &-other
+  }
+}
+
+impl Mul for &BigInt {
+  type Output = BigInt;
+
+  fn mul(self, other: &BigInt) -> BigInt {
+    let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+    for (i, &a) in self.magnitude.iter().enumerate() {
+      let mut carry = 0u64;
+      for (j, &b) in other.magnitude.iter().enumerate() {
+        let sum = product[i + j] as u64 + a as u64 * b as u64 + carry;
+        product[i + j] = sum as u32;
+        carry = sum >> 32;
+      }
+      product[i + other.magnitude.len()] = carry as u32;
+    }
+    BigInt::new(self.negative != other.negative, product)
+  }
+}
+
+impl fmt::Display for BigInt {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // Peel off nine decimal digits at a time, least significant first.
+    let mut chunks = Vec::new();
+    let mut magnitude = self.magnitude.clone();
+    while !magnitude.is_empty() {
+      chunks.push(div_rem_small(&mut magnitude, 1_000_000_000));
+    }
+
+    if self.negative {
+      write!(f, "-")?;
+    }
+    match chunks.split_last() {
+      Some((first, rest)) => {
+        write!(f, "{}", first)?;
+        for chunk in rest.iter().rev() {
+          write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+      }
+      None => write!(f, "0"),
+    }
+  }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+  a.len()
+    .cmp(&b.len())
+    .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+  let mut sum = Vec::with_capacity(long.len() + 1);
+  let mut carry = 0u64;
+  for (i, &limb) in long.iter().enumerate() {
+    let total = limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+    sum.push(total as u32);
+    carry = total >> 32;
+  }
+  sum.push(carry as u32);
+  sum
+}
+
+/// Computes `a - b` for `a >= b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+  let mut difference = Vec::with_capacity(a.len());
+  let mut borrow = 0i64;
+  for (i, &limb) in a.iter().enumerate() {
+    let mut total = limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+    borrow = (total < 0) as i64;
+    if total < 0 {
+      total += 1 << 32;
+    }
+    difference.push(total as u32);
+  }
+  difference
+}
+
+/// Computes `magnitude * factor + addend` in place.
+fn mul_small_add(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+  let mut carry = addend as u64;
+  for limb in magnitude.iter_mut() {
+    let total = *limb as u64 * factor as u64 + carry;
+    *limb = total as u32;
+    carry = total >> 32;
+  }
+  if carry > 0 {
+    magnitude.push(carry as u32);
+  }
+}
+
+/// Divides `magnitude` by `divisor` in place, returning the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+  let mut remainder = 0u64;
+  for limb in magnitude.iter_mut().rev() {
+    let total = remainder << 32 | *limb as u64;
+    *limb = (total / divisor as u64) as u32;
+    remainder = total % divisor as u64;
+  }
+  while magnitude.last() == Some(&0) {
+    magnitude.pop();
+  }
+  remainder as u32
+}
+
+/// Schoolbook binary long division of `a` by a nonzero `b`.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+  if let [divisor] = b {
+    let mut quotient = a.to_vec();
+    let remainder = div_rem_small(&mut quotient, *divisor);
+    return (quotient, vec![remainder]);
+  }
+
+  let mut quotient = vec![0u32; a.len()];
+  let mut remainder: Vec<u32> = Vec::with_capacity(b.len() + 1);
+  for bit in (0..a.len() * 32).rev() {
+    // remainder = remainder * 2 + next bit of a
+    let mut carry = a[bit / 32] >> (bit % 32) & 1;
+    for limb in remainder.iter_mut() {
+      let next = *limb >> 31;
+      *limb = *limb << 1 | carry;
+      carry = next;
+    }
+    if carry > 0 {
+      remainder.push(carry);
+    }
+
+    if cmp_magnitude(&remainder, b) != Ordering::Less {
+      remainder = sub_magnitude(&remainder, b);
+      while remainder.last() == Some(&0) {
+        remainder.pop();
+      }
+      quotient[bit / 32] |= 1 << (bit % 32);
+    }
+  }
+  (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn big(n: i64) -> BigInt {
+    BigInt::from(n)
+  }
+
+  fn parse(digits: &str) -> BigInt {
+    match digits.strip_prefix('-') {
+      Some(digits) => -&BigInt::parse_radix(digits, 10).unwrap(),
+      None => BigInt::parse_radix(digits, 10).unwrap(),
+    }
+  }
+
+  #[test]
+  fn div_rem_truncates_towards_zero() {
+    for (a, b, quotient, remainder) in [
+      (7, 2, 3, 1),
+      (-7, 2, -3, -1),
+      (7, -2, -3, 1),
+      (-7, -2, 3, -1),
+    ] {
+      assert_eq!(
+        big(a).div_rem(&big(b)),
+        Some((big(quotient), big(remainder))),
+        "{} / {}",
+        a,
+        b
+      );
+    }
+    assert_eq!(big(7).div_rem(&big(0)), None);
+  }
+
+  #[test]
+  fn div_mod_floor_takes_the_sign_of_the_divisor() {
+    for (a, b, quotient, remainder) in [
+      (7, 2, 3, 1),
+      (-7, 2, -4, 1),
+      (7, -2, -4, -1),
+      (-7, -2, 3, -1),
+      (-6, 2, -3, 0),
+    ] {
+      assert_eq!(
+        big(a).div_mod_floor(&big(b)),
+        Some((big(quotient), big(remainder))),
+        "{} // {}",
+        a,
+        b
+      );
+    }
+  }
+
+  #[test]
+  fn div_rem_across_limbs() {
+    let n = parse("-123456789012345678901234567890123456789");
+    let d = parse("98765432109876543210");
+    let (quotient, remainder) = n.div_rem(&d).unwrap();
+    assert_eq!(quotient, parse("-1249999988609375000"));
+    assert_eq!(remainder, parse("-15297067891529706789"));
+    assert_eq!(&(&quotient * &d) + &remainder, n);
+  }
+
+  #[test]
+  fn gcd_is_non_negative() {
+    assert_eq!(big(-12).gcd(&big(18)), big(6));
+    assert_eq!(big(12).gcd(&big(-18)), big(6));
+    assert_eq!(big(0).gcd(&big(-5)), big(5));
+    assert_eq!(big(0).gcd(&big(0)), big(0));
+  }
+
+  #[test]
+  fn display_round_trips() {
+    for digits in [
+      "0",
+      "7",
+      "-7",
+      "1000000000",
+      "-999999999",
+      "123456789012345678901234567890",
+    ] {
+      assert_eq!(parse(digits).to_string(), digits);
+    }
+    assert_eq!(
+      big(2).pow(100).to_string(),
+      "1267650600228229401496703205376"
+    );
+  }
+
+  #[test]
+  fn negative_zero_is_zero() {
+    let zero = -&big(0);
+    assert!(!zero.is_negative());
+    assert_eq!(zero, BigInt::default());
+    assert_eq!(&big(5) - &big(5), zero);
+  }
+
+  #[test]
+  fn to_i64_at_the_edges() {
+    assert_eq!(big(i64::MIN).to_i64(), Some(i64::MIN));
+    assert_eq!(big(i64::MAX).to_i64(), Some(i64::MAX));
+    assert_eq!((&big(i64::MAX) + &big(1)).to_i64(), None);
+    assert_eq!((&big(i64::MIN) - &big(1)).to_i64(), None);
+  }
+}
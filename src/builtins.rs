@@ -0,0 +1,294 @@
+use std::fmt;
+
+use crate::{
+  bigint::BigInt,
+  complex::Complex,
+  decimal::{Context, Decimal},
+  env::Env,
+  error::EvalError,
+  rational::Rational,
+  value::Value,
+};
+
+/// Most terms `sum` and `product` will add up, so that a range like
+/// `1, 10^12` reports an error instead of running for hours.
+const MAX_TERMS: i64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+  Exact(usize),
+  AtLeast(usize),
+}
+
+impl Arity {
+  pub fn accepts(self, count: usize) -> bool {
+    match self {
+      Arity::Exact(n) => count == n,
+      Arity::AtLeast(n) => count >= n,
+    }
+  }
+}
+
+impl fmt::Display for Arity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Arity::Exact(n) => write!(f, "{}", n),
+      Arity::AtLeast(n) => write!(f, "at least {}", n),
+    }
+  }
+}
+
+/// Which way `floor`, `ceil` and `round` take a value to an integer.
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+  Down,
+  Up,
+  Nearest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Builtin {
+  Unary(fn(f64) -> f64),
+  Binary(fn(f64, f64) -> f64),
+  /// Unary functions that keep integer arguments exact.
+  Exact(fn(&Value) -> Result<Value, EvalError>),
+  /// Unary functions that also take complex arguments, or give complex
+  /// results for some real ones.
+  Complex(fn(&Value) -> Result<Value, EvalError>),
+  /// Rounds to an integer, with the decimal context for `round`.
+  Round(Direction),
+  /// Takes one or more arguments.
+  Variadic(fn(&[Value]) -> Result<Value, EvalError>),
+  /// `map(f, list)`
+  Map,
+  /// `fold(f, init, list)`
+  Fold,
+  /// `sum(f, a, b)`, adding `f(i)` for integers `a..=b`.
+  Sum,
+  /// `product(f, a, b)`, multiplying `f(i)` for integers `a..=b`.
+  Product,
+}
+
+impl Builtin {
+  pub fn lookup(name: &str) -> Option<Self> {
+    let builtin = match name {
+      "sqrt" => Builtin::Complex(|value| {
+        complex_or(value, Complex::sqrt, |x| match x < 0.0 {
+          true => Value::Complex(Complex::new(0.0, (-x).sqrt())),
+          false => Value::Float(x.sqrt()),
+        })
+      }),
+      "abs" => Builtin::Exact(abs),
+      "sin" => Builtin::Unary(f64::sin),
+      "cos" => Builtin::Unary(f64::cos),
+      "tan" => Builtin::Unary(f64::tan),
+      "asin" => Builtin::Unary(f64::asin),
+      "acos" => Builtin::Unary(f64::acos),
+      "atan" => Builtin::Unary(f64::atan),
+      "sinh" => Builtin::Unary(f64::sinh),
+      "cosh" => Builtin::Unary(f64::cosh),
+      "tanh" => Builtin::Unary(f64::tanh),
+      "asinh" => Builtin::Unary(f64::asinh),
+      "acosh" => Builtin::Unary(f64::acosh),
+      "atanh" => Builtin::Unary(f64::atanh),
+      "ln" => Builtin::Complex(|value| {
+        complex_or(value, Complex::ln, |x| match x < 0.0 {
+          true => Value::Complex(Complex::from(x).ln()),
+          false => Value::Float(x.ln()),
+        })
+      }),
+      "log10" => Builtin::Unary(f64::log10),
+      "log2" => Builtin::Unary(f64::log2),
+      "exp" => Builtin::Complex(|value| complex_or(value, Complex::exp, |x| Value::Float(x.exp()))),
+      "arg" => Builtin::Complex(|value| match value {
+        Value::Complex(z) => Ok(Value::Float(z.arg())),
+        _ => Ok(Value::Float(0f64.atan2(value.as_float()?))),
+      }),
+      "conj" => Builtin::Complex(|value| match value {
+        Value::Complex(z) => Ok(Value::Complex(z.conj())),
+        _ => value.as_float().map(|_| value.clone()),
+      }),
+      "re" => Builtin::Complex(|value| match value {
+        Value::Complex(z) => Ok(Value::Float(z.re)),
+        _ => value.as_float().map(|_| value.clone()),
+      }),
+      "im" => Builtin::Complex(|value| match value {
+        Value::Complex(z) => Ok(Value::Float(z.im)),
+        _ => value.as_float().map(|_| Value::Int(0)),
+      }),
+      "floor" => Builtin::Round(Direction::Down),
+      "ceil" => Builtin::Round(Direction::Up),
+      "round" => Builtin::Round(Direction::Nearest),
+      "log" => Builtin::Binary(|base, x| x.log(base)),
+      "hypot" => Builtin::Binary(f64::hypot),
+      "atan2" => Builtin::Binary(f64::atan2),
+      "min" => Builtin::Variadic(|args| pick(args, |best, x| x < best)),
+      "max" => Builtin::Variadic(|args| pick(args, |best, x| x > best)),
+      "map" => Builtin::Map,
+      "fold" => Builtin::Fold,
+      "sum" => Builtin::Sum,
+      "product" => Builtin::Product,
+      _ => return None,
+    };
+    Some(builtin)
+  }
+
+  pub fn arity(self) -> Arity {
+    match self {
+      Builtin::Unary(_) | Builtin::Exact(_) | Builtin::Complex(_) | Builtin::Round(_) => {
+        Arity::Exact(1)
+      }
+      Builtin::Binary(_) => Arity::Exact(2),
+      Builtin::Variadic(_) => Arity::AtLeast(1),
+      Builtin::Map => Arity::Exact(2),
+      Builtin::Fold | Builtin::Sum | Builtin::Product => Arity::Exact(3),
+    }
+  }
+
+  /// Applies the builtin to arguments already checked against `arity`.
+  pub fn call(self, args: Vec<Value>, env: &mut Env) -> Result<Value, EvalError> {
+    match self {
+      Builtin::Unary(f) => Ok(Value::Float(f(args[0].as_float()?))),
+      Builtin::Binary(f) => Ok(Value::Float(f(args[0].as_float()?, args[1].as_float()?))),
+      Builtin::Exact(f) | Builtin::Complex(f) => f(&args[0]),
+      Builtin::Round(direction) => round_with(&args[0], direction, env.context()),
+      Builtin::Variadic(f) => f(&args),
+      Builtin::Map => args[1]
+        .as_list()?
+        .iter()
+        .map(|item| args[0].call(vec![item.clone()], env))
+        .collect::<Result<_, _>>()
+        .map(Value::List),
+      Builtin::Fold => args[2]
+        .as_list()?
+        .iter()
+        .try_fold(args[1].clone(), |acc, item| {
+          args[0].call(vec![acc, item.clone()], env)
+        }),
+      Builtin::Sum | Builtin::Product => {
+        let (from, to) = (integer(&args[1])?, integer(&args[2])?);
+        if to.saturating_sub(from) >= MAX_TERMS {
+          return Err(EvalError::TooManyTerms(MAX_TERMS));
+        }
+        let (op, mut total) = match self {
+          Builtin::Sum => ("+", env.literal(Value::Int(0))),
+          _ => ("*", env.literal(Value::Int(1))),
+        };
+        for i in from..=to {
+          let term = args[0].call(vec![env.literal(Value::Int(i))], env)?;
+          total = Value::binary(op, &total, &term, env.context())?;
+        }
+        Ok(total)
+      }
+    }
+  }
+}
+
+/// Applies `complex` to complex arguments and `real` to anything numeric.
+fn complex_or(
+  value: &Value,
+  complex: fn(Complex) -> Complex,
+  real: fn(f64) -> Value,
+) -> Result<Value, EvalError> {
+  match value {
+    Value::Complex(z) => Ok(Value::Complex(complex(*z))),
+    _ => Ok(real(value.as_float()?)),
+  }
+}
+
+fn integer(value: &Value) -> Result<i64, EvalError> {
+  let exact = match value {
+    Value::Int(n) => return Ok(*n),
+    Value::Big(n) => Some(n.clone()),
+    Value::Ratio(r) if r.is_integer() => Some(r.numer().clone()),
+    Value::Decimal(d) => d.to_integer(),
+    _ => None,
+  };
+  if let Some(n) = exact {
+    return n.to_i64().ok_or(EvalError::NotAnInteger(n.to_f64()));
+  }
+  let x = value.as_float()?;
+  if x.fract() != 0.0 || !x.is_finite() {
+    return Err(EvalError::NotAnInteger(x));
+  }
+  Ok(x as i64)
+}
+
+fn abs(value: &Value) -> Result<Value, EvalError> {
+  match value {
+    Value::Int(n) => n.checked_abs().map(Value::Int).ok_or(EvalError::Overflow),
+    Value::Big(n) => Ok(Value::Big(n.abs())),
+    Value::Ratio(r) => Ok(Value::Ratio(r.abs())),
+    Value::Decimal(d) => Ok(Value::Decimal(d.abs())),
+    Value::Complex(z) => Ok(Value::Float(z.abs())),
+    _ => Ok(Value::Float(value.as_float()?.abs())),
+  }
+}
+
+/// Rounds to an integer in `direction`. Fractions and decimals stay exact,
+/// with `round` on a decimal following the context's rounding mode, and a
+/// rounded float becomes an integer when it fits in one.
+fn round_with(value: &Value, direction: Direction, context: Context) -> Result<Value, EvalError> {
+  let f = match value {
+    Value::Int(_) | Value::Big(_) => return Ok(value.clone()),
+    Value::Ratio(r) => {
+      let rounded = match direction {
+        Direction::Down => r.floor(),
+        Direction::Up => -&(-r).floor(),
+        // Halves go away from zero, as with `f64::round`.
+        Direction::Nearest => {
+          let half = Rational::new(BigInt::from(1), BigInt::from(2)).expect("two is not zero");
+          let magnitude = (&r.abs() + &half).floor();
+          if r.numer().is_negative() {
+            -&magnitude
+          } else {
+            magnitude
+          }
+        }
+      };
+      return Ok(Value::Ratio(Rational::from(rounded)));
+    }
+    Value::Decimal(d) => {
+      let floor = |d: &Decimal| {
+        let (quotient, _) = d
+          .div_mod_floor(&Decimal::from(BigInt::from(1)))
+          .expect("one is not zero");
+        quotient
+      };
+      let rounded = match direction {
+        Direction::Down => floor(d),
+        Direction::Up => -&floor(&-d),
+        Direction::Nearest => d.round(Context {
+          precision: 0,
+          ..context
+        }),
+      };
+      return Ok(Value::Decimal(rounded));
+    }
+    _ => match direction {
+      Direction::Down => f64::floor,
+      Direction::Up => f64::ceil,
+      Direction::Nearest => f64::round,
+    },
+  };
+  let x = f(value.as_float()?);
+  if (i64::MIN as f64..i64::MAX as f64).contains(&x) {
+    Ok(Value::Int(x as i64))
+  } else {
+    Ok(Value::Float(x))
+  }
+}
+
+/// Returns the argument `better` prefers over all others, keeping its type.
+fn pick(args: &[Value], better: fn(f64, f64) -> bool) -> Result<Value, EvalError> {
+  let mut best = &args[0];
+  let mut best_value = best.as_float()?;
+  for arg in &args[1..] {
+    let value = arg.as_float()?;
+    if better(best_value, value) {
+      best = arg;
+      best_value = value;
+    }
+  }
+  Ok(best.clone())
+}
@@ -0,0 +1,137 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A complex number with `f64` parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+  pub re: f64,
+  pub im: f64,
+}
+
+impl Complex {
+  /// The imaginary unit.
+  pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+  pub fn new(re: f64, im: f64) -> Self {
+    Self { re, im }
+  }
+
+  pub fn from_polar(r: f64, theta: f64) -> Self {
+    Self::new(r * theta.cos(), r * theta.sin())
+  }
+
+  pub fn is_zero(self) -> bool {
+    self.re == 0.0 && self.im == 0.0
+  }
+
+  /// The modulus `|z|`.
+  pub fn abs(self) -> f64 {
+    self.re.hypot(self.im)
+  }
+
+  /// The argument in `(-pi, pi]`.
+  pub fn arg(self) -> f64 {
+    self.im.atan2(self.re)
+  }
+
+  pub fn conj(self) -> Self {
+    Self::new(self.re, -self.im)
+  }
+
+  pub fn exp(self) -> Self {
+    Self::from_polar(self.re.exp(), self.im)
+  }
+
+  /// The principal natural logarithm.
+  pub fn ln(self) -> Self {
+    Self::new(self.abs().ln(), self.arg())
+  }
+
+  /// The principal square root, with a non-negative real part.
+  pub fn sqrt(self) -> Self {
+    Self::from_polar(self.abs().sqrt(), self.arg() / 2.0)
+  }
+
+  /// Raises to an integer power by repeated squaring, which keeps results
+  /// like `i^2 = -1` free of rounding noise.
+  pub fn powi(self, exponent: i32) -> Self {
+    let mut base = self;
+    let mut result = Self::new(1.0, 0.0);
+    let mut n = exponent.unsigned_abs();
+    while n > 0 {
+      if n & 1 == 1 {
+        result = result * base;
+      }
+      base = base * base;
+      n >>= 1;
+    }
+    if exponent < 0 {
+      Self::new(1.0, 0.0) / result
+    } else {
+      result
+    }
+  }
+
+  pub fn powc(self, exponent: Self) -> Self {
+    if self.is_zero() {
+      return if exponent.is_zero() {
+        Self::new(1.0, 0.0)
+      } else {
+        self
+      };
+    }
+    (exponent * self.ln()).exp()
+  }
+}
+
+impl From<f64> for Complex {
+  fn from(re: f64) -> Self {
+    Self::new(re, 0.0)
+  }
+}
+
+impl Neg for Complex {
+  type Output = Complex;
+
+  fn neg(self) -> Complex {
+    Complex::new(-self.re, -self.im)
+  }
+}
+
+impl Add for Complex {
+  type Output = Complex;
+
+  fn add(self, other: Complex) -> Complex {
+    Complex::new(self.re + other.re, self.im + other.im)
+  }
+}
+
+impl Sub for Complex {
+  type Output = Complex;
+
+  fn sub(self, other: Complex) -> Complex {
+    Complex::new(self.re - other.re, self.im - other.im)
+  }
+}
+
+impl Mul for Complex {
+  type Output = Complex;
+
+  fn mul(self, other: Complex) -> Complex {
+    Complex::new(
+      self.re * other.re - self.im * other.im,
+      self.re * other.im + self.im * other.re,
+    )
+  }
+}
+
+impl Div for Complex {
+  type Output = Complex;
+
+  fn div(self, other: Complex) -> Complex {
+    let denom = other.re * other.re + other.im * other.im;
+    Complex::new(
+      (self.re * other.re + self.im * other.im) / denom,
+      (self.im * other.re - self.re * other.im) / denom,
+    )
+  }
+}
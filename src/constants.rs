@@ -0,0 +1,26 @@
+use std::f64::consts;
+
+/// Resolves a named constant. The CODATA physical constants are only
+/// visible when `physics` is enabled, since names like `c` and `h` are
+/// common variable names otherwise.
+pub fn lookup(name: &str, physics: bool) -> Option<f64> {
+  let value = match name {
+    "pi" => consts::PI,
+    "e" => consts::E,
+    "tau" => consts::TAU,
+    "phi" => 1.618_033_988_749_895,
+    _ if !physics => return None,
+    // Speed of light in vacuum, m/s.
+    "c" => 299_792_458.0,
+    // Newtonian constant of gravitation, m^3/(kg s^2).
+    "G" => 6.674_30e-11,
+    // Planck constant, J s.
+    "h" => 6.626_070_15e-34,
+    // Boltzmann constant, J/K.
+    "k_B" => 1.380_649e-23,
+    // Avogadro constant, 1/mol.
+    "N_A" => 6.022_140_76e23,
+    _ => return None,
+  };
+  Some(value)
+}
@@ -0,0 +1,440 @@
+use std::{cmp::Ordering, fmt, ops::Neg};
+
+use crate::{bigint::BigInt, rational::Rational};
+
+/// Largest exponent magnitude [`Decimal::parse`] expands, so a literal like
+/// `1e999999999` doesn't build a billion-digit coefficient.
+const MAX_EXPONENT: u64 = 10_000;
+
+/// How results are rounded to the precision of a [`Context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+  /// Ties go to the even neighbour, as in banking.
+  HalfEven,
+  /// Ties go away from zero.
+  HalfUp,
+  /// Extra digits are dropped, rounding towards zero.
+  Truncate,
+}
+
+impl Rounding {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "half-even" => Some(Rounding::HalfEven),
+      "half-up" => Some(Rounding::HalfUp),
+      "truncate" => Some(Rounding::Truncate),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Rounding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Rounding::HalfEven => write!(f, "half-even"),
+      Rounding::HalfUp => write!(f, "half-up"),
+      Rounding::Truncate => write!(f, "truncate"),
+    }
+  }
+}
+
+/// Largest [`Context::precision`] accepted from the user. Every division
+/// computes that many digits, so much larger values make it crawl.
+pub const MAX_PRECISION: u32 = 1_000;
+
+/// The number of digits kept after the decimal point, and how to round
+/// away the rest.
+#[derive(Debug, Clone, Copy)]
+pub struct Context {
+  pub precision: u32,
+  pub rounding: Rounding,
+}
+
+impl Default for Context {
+  fn default() -> Self {
+    Self {
+      precision: 20,
+      rounding: Rounding::HalfEven,
+    }
+  }
+}
+
+/// A decimal number `coefficient * 10^-scale`, so values like `0.1` are
+/// represented exactly.
+#[derive(Debug, Clone)]
+pub struct Decimal {
+  coefficient: BigInt,
+  scale: u32,
+}
+
+impl Decimal {
+  pub fn new(coefficient: BigInt, scale: u32) -> Self {
+    Self { coefficient, scale }
+  }
+
+  /// Parses an unsigned literal like `1.25`, `.5` or `6.02e23` exactly,
+  /// without trailing zeros after the point. Returns `None` for malformed
+  /// text or an exponent beyond [`MAX_EXPONENT`].
+  pub fn parse(text: &str) -> Option<Self> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+      Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+      None => (text, 0),
+    };
+    if exponent.unsigned_abs() > MAX_EXPONENT {
+      return None;
+    }
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let coefficient = BigInt::parse_radix(&format!("{}{}", whole, fraction), 10)?;
+
+    let exponent = exponent - fraction.len() as i64;
+    let decimal = if exponent >= 0 {
+      Self::new(&coefficient * &pow10(exponent as u32), 0)
+    } else {
+      Self::new(coefficient, u32::try_from(-exponent).ok()?)
+    };
+    Some(decimal.normalize())
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.coefficient.is_zero()
+  }
+
+  pub fn abs(&self) -> Self {
+    Self::new(self.coefficient.abs(), self.scale)
+  }
+
+  /// Divides by 100 exactly, for the `%` postfix operator.
+  pub fn percent(&self) -> Self {
+    Self::new(self.coefficient.clone(), self.scale + 2)
+  }
+
+  /// The value as an integer, if it has no fractional part.
+  pub fn to_integer(&self) -> Option<BigInt> {
+    let (quotient, remainder) = self.coefficient.div_rem(&pow10(self.scale))?;
+    remainder.is_zero().then_some(quotient)
+  }
+
+  /// The exact fraction, so the literal `0.1` becomes `1/10` rather than the
+  /// nearest binary fraction.
+  pub fn to_rational(&self) -> Rational {
+    Rational::new(self.coefficient.clone(), pow10(self.scale))
+      .expect("powers of ten are never zero")
+  }
+
+  pub fn to_f64(&self) -> f64 {
+    self.to_string().parse().unwrap_or(f64::NAN)
+  }
+
+  /// Rounds to at most `context.precision` digits after the point.
+  pub fn round(&self, context: Context) -> Self {
+    if self.scale <= context.precision {
+      return self.clone();
+    }
+    let divisor = pow10(self.scale - context.precision);
+    let (quotient, remainder) = self
+      .coefficient
+      .div_rem(&divisor)
+      .expect("powers of ten are never zero");
+
+    let half = (&remainder.abs() * &BigInt::from(2)).cmp(&divisor);
+    let away = match (context.rounding, half) {
+      (Rounding::Truncate, _) | (_, Ordering::Less) => false,
+      (_, Ordering::Greater) | (Rounding::HalfUp, Ordering::Equal) => true,
+      (Rounding::HalfEven, Ordering::Equal) => {
+        let (_, parity) = quotient.div_rem(&BigInt::from(2)).expect("two is not zero");
+        !parity.is_zero()
+      }
+    };
+
+    let step = BigInt::from(if self.coefficient.is_negative() {
+      -1
+    } else {
+      1
+    });
+    let coefficient = if away { &quotient + &step } else { quotient };
+    Self::new(coefficient, context.precision)
+  }
+
+  /// Removes trailing zeros after the point, so `0.2500` becomes `0.25`.
+  fn normalize(mut self) -> Self {
+    let ten = BigInt::from(10);
+    while self.scale > 0 {
+      match self.coefficient.div_rem(&ten) {
+        Some((quotient, remainder)) if remainder.is_zero() => {
+          self.coefficient = quotient;
+          self.scale -= 1;
+        }
+        _ => break,
+      }
+    }
+    self
+  }
+
+  /// Both coefficients at a common scale.
+  fn align(&self, other: &Self) -> (BigInt, BigInt, u32) {
+    let scale = self.scale.max(other.scale);
+    (
+      &self.coefficient * &pow10(scale - self.scale),
+      &other.coefficient * &pow10(scale - other.scale),
+      scale,
+    )
+  }
+
+  pub fn add(&self, other: &Self, context: Context) -> Self {
+    let (a, b, scale) = self.align(other);
+    Self::new(&a + &b, scale).round(context)
+  }
+
+  pub fn sub(&self, other: &Self, context: Context) -> Self {
+    let (a, b, scale) = self.align(other);
+    Self::new(&a - &b, scale).round(context)
+  }
+
+  pub fn mul(&self, other: &Self, context: Context) -> Self {
+    Self::new(
+      &self.coefficient * &other.coefficient,
+      self.scale + other.scale,
+    )
+    .round(context)
+  }
+
+  /// Returns `None` when dividing by zero, or when the precision is too
+  /// large to represent the guard digits.
+  pub fn div(&self, other: &Self, context: Context) -> Option<Self> {
+    // One guard digit beyond the precision, so rounding sees the tie.
+    let (a, b, _) = self.align(other);
+    let scale = context.precision.checked_add(1)?;
+    let (quotient, remainder) = (&a * &pow10(scale)).div_rem(&b)?;
+    // A nonzero remainder means the true value lies past the guard digit,
+    // which matters when the guard digit alone looks like an exact tie.
+    let sticky = match (remainder.is_zero(), quotient.is_negative()) {
+      (true, _) => BigInt::from(0),
+      (false, false) => BigInt::from(1),
+      (false, true) => BigInt::from(-1),
+    };
+    let exact = Self::new(
+      &(&quotient * &BigInt::from(10)) + &sticky,
+      scale.checked_add(1)?,
+    );
+    Some(exact.round(context).normalize())
+  }
+
+  /// Floor division and its remainder, both exact. Returns `None` when
+  /// dividing by zero.
+  pub fn div_mod_floor(&self, other: &Self) -> Option<(Self, Self)> {
+    let (a, b, scale) = self.align(other);
+    let (quotient, remainder) = a.div_mod_floor(&b)?;
+    Some((Self::new(quotient, 0), Self::new(remainder, scale)))
+  }
+
+  /// Raises to an integer power, or `None` for a negative power of zero.
+  pub fn pow(&self, exponent: i64, context: Context) -> Option<Self> {
+    let power = u32::try_from(exponent.unsigned_abs()).ok()?;
+    let result = Self::new(self.coefficient.pow(power), self.scale.checked_mul(power)?);
+    if exponent < 0 {
+      Self::new(BigInt::from(1), 0).div(&result, context)
+    } else {
+      Some(result.round(context))
+    }
+  }
+
+  /// Roughly the number of bits needed for the coefficient and for the
+  /// power of ten given by the scale, to bound the cost of `pow`.
+  pub fn bits(&self) -> u64 {
+    self.coefficient.bits().max(self.scale as u64 * 4)
+  }
+}
+
+impl From<BigInt> for Decimal {
+  fn from(n: BigInt) -> Self {
+    Self::new(n, 0)
+  }
+}
+
+impl Neg for &Decimal {
+  type Output = Decimal;
+
+  fn neg(self) -> Decimal {
+    Decimal::new(-&self.coefficient, self.scale)
+  }
+}
+
+impl PartialEq for Decimal {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for Decimal {}
+
+impl Ord for Decimal {
+  fn cmp(&self, other: &Self) -> Ordering {
+    let (a, b, _) = self.align(other);
+    a.cmp(&b)
+  }
+}
+
+impl PartialOrd for Decimal {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for Decimal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let digits = self.coefficient.abs().to_string();
+    let scale = self.scale as usize;
+    let sign = if self.coefficient.is_negative() {
+      "-"
+    } else {
+      ""
+    };
+    if scale == 0 {
+      return write!(f, "{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    write!(f, "{}{}.{}", sign, whole, fraction)
+  }
+}
+
+fn pow10(exponent: u32) -> BigInt {
+  BigInt::from(10).pow(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decimal(text: &str) -> Decimal {
+    match text.strip_prefix('-') {
+      Some(text) => -&Decimal::parse(text).unwrap(),
+      None => Decimal::parse(text).unwrap(),
+    }
+  }
+
+  fn context(precision: u32, rounding: Rounding) -> Context {
+    Context {
+      precision,
+      rounding,
+    }
+  }
+
+  #[test]
+  fn parse_keeps_every_digit() {
+    for (text, expected) in [
+      ("1234567890.123456789", "1234567890.123456789"),
+      ("0.1234567890123456789", "0.1234567890123456789"),
+      (".5", "0.5"),
+      ("5.", "5"),
+      ("1.50", "1.5"),
+      ("2.5e-3", "0.0025"),
+      ("6.02E23", "602000000000000000000000"),
+      ("1e+2", "100"),
+    ] {
+      assert_eq!(
+        Decimal::parse(text).unwrap().to_string(),
+        expected,
+        "{}",
+        text
+      );
+    }
+    for text in [".", "1e", "1e+", "1.2.3", "1e99999"] {
+      assert!(Decimal::parse(text).is_none(), "{}", text);
+    }
+  }
+
+  #[test]
+  fn to_rational_is_exact() {
+    let r = decimal("0.1234567890123456789").to_rational();
+    assert_eq!(r.to_string(), "1234567890123456789/10000000000000000000");
+    assert_eq!(decimal("2.50").to_rational().to_string(), "5/2");
+    assert_eq!(decimal("-0.75").to_rational().to_string(), "-3/4");
+  }
+
+  #[test]
+  fn round_breaks_ties_by_mode() {
+    let cases = [
+      ("0.5", "0", "1", "0"),
+      ("1.5", "2", "2", "1"),
+      ("2.5", "2", "3", "2"),
+      ("-2.5", "-2", "-3", "-2"),
+      ("-0.5", "0", "-1", "0"),
+      ("2.51", "3", "3", "2"),
+      ("-2.49", "-2", "-2", "-2"),
+    ];
+    for (value, half_even, half_up, truncate) in cases {
+      for (rounding, expected) in [
+        (Rounding::HalfEven, half_even),
+        (Rounding::HalfUp, half_up),
+        (Rounding::Truncate, truncate),
+      ] {
+        let rounded = decimal(value).round(context(0, rounding));
+        assert_eq!(rounded.to_string(), expected, "{} {}", value, rounding);
+      }
+    }
+  }
+
+  #[test]
+  fn div_rounds_at_the_precision() {
+    let one = decimal("1");
+    let three = decimal("3");
+    let half_even = context(5, Rounding::HalfEven);
+    assert_eq!(one.div(&three, half_even).unwrap().to_string(), "0.33333");
+    assert_eq!(
+      decimal("2").div(&three, half_even).unwrap().to_string(),
+      "0.66667"
+    );
+    let truncate = context(5, Rounding::Truncate);
+    assert_eq!(
+      decimal("-2").div(&three, truncate).unwrap().to_string(),
+      "-0.66666"
+    );
+    assert_eq!(
+      one
+        .div(&decimal("4"), Context::default())
+        .unwrap()
+        .to_string(),
+      "0.25"
+    );
+    assert!(one.div(&decimal("0"), half_even).is_none());
+  }
+
+  #[test]
+  fn div_sees_digits_past_the_guard_digit() {
+    let one = decimal("1");
+    let tenths = context(1, Rounding::HalfEven);
+    assert_eq!(
+      decimal("0.25").div(&one, tenths).unwrap().to_string(),
+      "0.2"
+    );
+    assert_eq!(
+      decimal("0.2500001").div(&one, tenths).unwrap().to_string(),
+      "0.3"
+    );
+    assert_eq!(
+      decimal("-0.2500001").div(&one, tenths).unwrap().to_string(),
+      "-0.3"
+    );
+  }
+
+  #[test]
+  fn div_rejects_precision_without_room_for_guard_digits() {
+    let huge = context(u32::MAX, Rounding::HalfEven);
+    assert!(decimal("1").div(&decimal("3"), huge).is_none());
+  }
+
+  #[test]
+  fn arithmetic_rounds_and_compares_by_value() {
+    let cents = context(2, Rounding::HalfEven);
+    assert_eq!(
+      decimal("0.125").mul(&decimal("1"), cents).to_string(),
+      "0.12"
+    );
+    assert_eq!(decimal("0.1").add(&decimal("0.2"), cents), decimal("0.3"));
+    assert_eq!(Decimal::new(BigInt::from(150), 2), decimal("1.5"));
+    assert!(decimal("-0.01") < decimal("0"));
+    assert_eq!(decimal("5").percent().to_string(), "0.05");
+  }
+}
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+  Expr,
+  bigint::BigInt,
+  builtins::Builtin,
+  complex::Complex,
+  constants,
+  decimal::{Context, Decimal},
+  error::EvalError,
+  rational::Rational,
+  value::Value,
+};
+
+/// Default limit on nested user function calls.
+const DEFAULT_MAX_DEPTH: usize = 200;
+
+/// Limit on nested subexpressions being evaluated at once, across all
+/// function calls. The evaluator recurses once per level, so this is what
+/// keeps deep recursion from overflowing the REPL thread's stack, whatever
+/// the call depth limit is set to. A chain like `1 + 1 + … + 1` parses as
+/// nested operations too, so it counts one level per operator.
+const MAX_EVAL_NESTING: usize = 10_000;
+
+/// How numeric literals are represented, and so which [`Number`]
+/// implementation arithmetic uses.
+///
+/// [`Number`]: crate::number::Number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+  /// 64-bit integers that report overflow, and floats.
+  #[default]
+  Int,
+  /// Floats only, even for integer literals.
+  Float,
+  /// Unbounded integers, and floats.
+  Bignum,
+  /// Exact fractions, with literals like `0.1` read as `1/10`.
+  Rational,
+  /// Decimals rounded to the precision of a [`Context`].
+  Decimal,
+}
+
+impl Mode {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "int" => Some(Mode::Int),
+      "float" => Some(Mode::Float),
+      "bignum" => Some(Mode::Bignum),
+      "rational" => Some(Mode::Rational),
+      "decimal" => Some(Mode::Decimal),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Mode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Mode::Int => write!(f, "int"),
+      Mode::Float => write!(f, "float"),
+      Mode::Bignum => write!(f, "bignum"),
+      Mode::Rational => write!(f, "rational"),
+      Mode::Decimal => write!(f, "decimal"),
+    }
+  }
+}
+
+/// A function defined with `f(x, y) = body` or a `x => body` lambda.
+#[derive(Debug)]
+pub struct Function {
+  pub name: String,
+  pub params: Vec<String>,
+  pub body: Expr,
+  /// Locals of the enclosing call when a lambda was created.
+  pub captured: HashMap<String, Value>,
+}
+
+/// Variables and functions that live across REPL lines.
+#[derive(Debug)]
+pub struct Env {
+  vars: HashMap<String, Value>,
+  funcs: HashMap<String, Rc<Function>>,
+  /// Parameters of the user function calls currently being evaluated.
+  frames: Vec<HashMap<String, Value>>,
+  max_depth: usize,
+  /// The number of expressions currently being evaluated.
+  nesting: usize,
+  history: Vec<Value>,
+  physics: bool,
+  mode: Mode,
+  /// Whether fractions are shown with their decimal approximation.
+  approximate: bool,
+  context: Context,
+}
+
+impl Default for Env {
+  fn default() -> Self {
+    Self {
+      vars: HashMap::new(),
+      funcs: HashMap::new(),
+      frames: Vec::new(),
+      max_depth: DEFAULT_MAX_DEPTH,
+      nesting: 0,
+      history: Vec::new(),
+      physics: false,
+      mode: Mode::default(),
+      approximate: false,
+      context: Context::default(),
+    }
+  }
+}
+
+impl Env {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Makes the physical constants in [`constants::lookup`] visible.
+  pub fn enable_physics(&mut self) {
+    self.physics = true;
+  }
+
+  pub fn mode(&self) -> Mode {
+    self.mode
+  }
+
+  pub fn set_mode(&mut self, mode: Mode) {
+    self.mode = mode;
+  }
+
+  /// Precision and rounding for decimal mode.
+  pub fn context(&self) -> Context {
+    self.context
+  }
+
+  pub fn context_mut(&mut self) -> &mut Context {
+    &mut self.context
+  }
+
+  pub fn approximate(&self) -> bool {
+    self.approximate
+  }
+
+  pub fn toggle_approximate(&mut self) -> bool {
+    self.approximate = !self.approximate;
+    self.approximate
+  }
+
+  /// Converts a numeric literal to the representation of the current mode.
+  /// Integers too large for an `i64` are floats in int mode, and literals
+  /// with a fraction or exponent, which the lexer keeps as exact decimals,
+  /// are floats outside rational and decimal modes.
+  pub fn literal(&self, value: Value) -> Value {
+    match (self.mode, value) {
+      (Mode::Int | Mode::Float, Value::Big(n)) => Value::Float(n.to_f64()),
+      (Mode::Int | Mode::Float | Mode::Bignum, Value::Decimal(d)) => Value::Float(d.to_f64()),
+      (Mode::Float, Value::Int(n)) => Value::Float(n as f64),
+      (Mode::Bignum, Value::Int(n)) => Value::Big(BigInt::from(n)),
+      (Mode::Rational, Value::Int(n)) => Value::Ratio(Rational::from(BigInt::from(n))),
+      (Mode::Rational, Value::Big(n)) => Value::Ratio(Rational::from(n)),
+      (Mode::Rational, Value::Decimal(d)) => Value::Ratio(d.to_rational()),
+      (Mode::Decimal, Value::Int(n)) => Value::Decimal(Decimal::from(BigInt::from(n))),
+      (Mode::Decimal, Value::Big(n)) => Value::Decimal(Decimal::from(n)),
+      (_, value) => value,
+    }
+  }
+
+  pub fn set_max_depth(&mut self, max_depth: usize) {
+    self.max_depth = max_depth;
+  }
+
+  /// Looks up a variable or constant. `$n` names the n-th recorded result,
+  /// and `i` is the imaginary unit unless bound to something else.
+  pub fn get(&self, name: &str) -> Option<Value> {
+    if let Some(value) = constants::lookup(name, self.physics) {
+      return Some(Value::Float(value));
+    }
+    if let Some(value) = self.frames.last().and_then(|frame| frame.get(name)) {
+      return Some(value.clone());
+    }
+
+    match name.strip_prefix('$') {
+      Some(index) => {
+        let index: usize = index.parse().ok()?;
+        self.history.get(index.checked_sub(1)?).cloned()
+      }
+      None => self
+        .vars
+        .get(name)
+        .cloned()
+        .or_else(|| (name == "i").then_some(Value::Complex(Complex::I))),
+    }
+  }
+
+  /// Assigns a variable, local to the current function call if inside one.
+  pub fn set(&mut self, name: &str, value: Value) -> Result<(), EvalError> {
+    if constants::lookup(name, self.physics).is_some() {
+      return Err(EvalError::AssignToConstant(name.to_string()));
+    }
+
+    let scope = self.frames.last_mut().unwrap_or(&mut self.vars);
+    scope.insert(name.to_string(), value);
+    Ok(())
+  }
+
+  /// The locals of the innermost function call, for lambdas to capture.
+  pub fn locals(&self) -> HashMap<String, Value> {
+    self.frames.last().cloned().unwrap_or_default()
+  }
+
+  pub fn function(&self, name: &str) -> Option<Rc<Function>> {
+    self.funcs.get(name).cloned()
+  }
+
+  pub fn define(&mut self, name: &str, params: Vec<String>, body: Expr) -> Result<(), EvalError> {
+    if Builtin::lookup(name).is_some() {
+      return Err(EvalError::RedefineBuiltin(name.to_string()));
+    }
+    self.check_params(&params)?;
+
+    let function = Function {
+      name: name.to_string(),
+      params,
+      body,
+      captured: HashMap::new(),
+    };
+    self.funcs.insert(name.to_string(), Rc::new(function));
+    Ok(())
+  }
+
+  /// Rejects parameter lists that shadow a constant or repeat a name.
+  pub fn check_params(&self, params: &[String]) -> Result<(), EvalError> {
+    for (i, param) in params.iter().enumerate() {
+      if constants::lookup(param, self.physics).is_some() {
+        return Err(EvalError::AssignToConstant(param.clone()));
+      }
+      if params[..i].contains(param) {
+        return Err(EvalError::DuplicateParameter(param.clone()));
+      }
+    }
+    Ok(())
+  }
+
+  /// Starts a user function call with its parameters bound to `locals`.
+  pub fn enter(&mut self, locals: HashMap<String, Value>) -> Result<(), EvalError> {
+    if self.frames.len() >= self.max_depth {
+      return Err(EvalError::RecursionLimit(self.max_depth));
+    }
+    self.frames.push(locals);
+    Ok(())
+  }
+
+  pub fn leave(&mut self) {
+    self.frames.pop();
+  }
+
+  /// Starts evaluating a subexpression.
+  pub fn descend(&mut self) -> Result<(), EvalError> {
+    if self.nesting >= MAX_EVAL_NESTING {
+      return Err(EvalError::NestingLimit(MAX_EVAL_NESTING));
+    }
+    self.nesting += 1;
+    Ok(())
+  }
+
+  pub fn ascend(&mut self) {
+    self.nesting -= 1;
+  }
+
+  /// Stores a REPL result as `ans`, `_` and the next `$n`, returning `n`.
+  pub fn record(&mut self, value: Value) -> usize {
+    self.history.push(value.clone());
+    self.vars.insert("ans".to_string(), value.clone());
+    self.vars.insert("_".to_string(), value);
+    self.history.len()
+  }
+}
@@ -0,0 +1,162 @@
+use std::{error::Error, fmt};
+
+use crate::{Span, Token, builtins::Arity};
+
+/// An error together with the span of input it refers to.
+#[derive(Debug, Clone)]
+pub struct Diagnostic<E> {
+  pub error: E,
+  pub span: Span,
+}
+
+impl<E: fmt::Display> Diagnostic<E> {
+  /// Renders the error under the offending part of `source`:
+  ///
+  /// ```text
+  /// error: division by zero
+  ///   1 / (2 - 2)
+  ///        ^~~~~
+  /// ```
+  pub fn render(&self, source: &str) -> String {
+    let offset = source[..self.span.start].chars().count();
+    let width = source[self.span.start..self.span.end].chars().count();
+
+    format!(
+      "error: {}\n  {}\n  {}^{}",
+      self.error,
+      source,
+      " ".repeat(offset),
+      "~".repeat(width.saturating_sub(1))
+    )
+  }
+}
+
+impl<E: fmt::Display> fmt::Display for Diagnostic<E> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} at {}..{}",
+      self.error, self.span.start, self.span.end
+    )
+  }
+}
+
+impl<E: Error> Error for Diagnostic<E> {}
+
+#[derive(Debug, Clone)]
+pub enum ParseError {
+  UnexpectedChar(char),
+  InvalidNumber(String),
+  UnexpectedToken(Token),
+  UnexpectedEof,
+  MissingCloseParen,
+  MissingCloseBracket,
+  UnexpectedCloseParen,
+  InvalidAssignTarget,
+  InvalidLambdaParams,
+  TooDeeplyNested,
+}
+
+impl ParseError {
+  pub fn at(self, span: Span) -> Diagnostic<Self> {
+    Diagnostic { error: self, span }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ParseError::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+      ParseError::InvalidNumber(literal) => write!(f, "invalid number {:?}", literal),
+      ParseError::UnexpectedToken(token) => write!(f, "unexpected token {}", token),
+      ParseError::UnexpectedEof => write!(f, "unexpected end of input"),
+      ParseError::MissingCloseParen => write!(f, "unbalanced parentheses: missing ')'"),
+      ParseError::MissingCloseBracket => write!(f, "unbalanced brackets: missing ']'"),
+      ParseError::UnexpectedCloseParen => write!(f, "unbalanced parentheses: unexpected ')'"),
+      ParseError::InvalidAssignTarget => write!(f, "can only assign to a variable"),
+      ParseError::InvalidLambdaParams => write!(f, "lambda parameters must be names"),
+      ParseError::TooDeeplyNested => write!(f, "expression is nested too deeply"),
+    }
+  }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone)]
+pub enum EvalError {
+  DivisionByZero,
+  Overflow,
+  UnknownOperator(&'static str),
+  UnknownVariable(String),
+  AssignToConstant(String),
+  RedefineBuiltin(String),
+  DuplicateParameter(String),
+  NestedDefinition,
+  RecursionLimit(usize),
+  NestingLimit(usize),
+  TooManyTerms(i64),
+  TypeMismatch {
+    expected: &'static str,
+    found: &'static str,
+  },
+  NotAnInteger(f64),
+  UnknownFunction(String),
+  WrongArity {
+    name: String,
+    expected: Arity,
+    found: usize,
+  },
+  InvalidOperand {
+    op: &'static str,
+    value: f64,
+  },
+}
+
+impl EvalError {
+  pub fn at(self, span: Span) -> Diagnostic<Self> {
+    Diagnostic { error: self, span }
+  }
+}
+
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EvalError::DivisionByZero => write!(f, "division by zero"),
+      EvalError::Overflow => write!(f, "integer overflow"),
+      EvalError::UnknownOperator(op) => write!(f, "unknown operator '{}'", op),
+      EvalError::UnknownVariable(name) => write!(f, "unknown variable '{}'", name),
+      EvalError::AssignToConstant(name) => write!(f, "cannot assign to constant '{}'", name),
+      EvalError::RedefineBuiltin(name) => write!(f, "cannot redefine built-in function '{}'", name),
+      EvalError::DuplicateParameter(name) => write!(f, "duplicate parameter '{}'", name),
+      EvalError::NestedDefinition => write!(f, "functions can only be defined at the top level"),
+      EvalError::RecursionLimit(depth) => {
+        write!(f, "maximum recursion depth of {} exceeded", depth)
+      }
+      EvalError::NestingLimit(depth) => {
+        write!(f, "maximum expression nesting of {} exceeded", depth)
+      }
+      EvalError::TooManyTerms(limit) => write!(f, "too many terms, the limit is {}", limit),
+      EvalError::TypeMismatch { expected, found } => {
+        write!(f, "type mismatch: expected {}, found {}", expected, found)
+      }
+      EvalError::NotAnInteger(value) => write!(f, "expected an integer, found {}", value),
+
+      EvalError::UnknownFunction(name) => write!(f, "unknown function '{}'", name),
+      EvalError::WrongArity {
+        name,
+        expected,
+        found,
+      } => write!(
+        f,
+        "'{}' takes {} argument(s) but {} were given",
+        name, expected, found
+      ),
+
+      EvalError::InvalidOperand { op, value } => {
+        write!(f, "operator '{}' is not defined for {}", op, value)
+      }
+    }
+  }
+}
+
+impl Error for EvalError {}
@@ -1,80 +1,569 @@
+mod bigint;
+mod builtins;
+mod complex;
+mod constants;
+mod decimal;
+mod env;
+mod error;
+mod number;
+mod rational;
+mod value;
+
 use std::{
   fmt,
   io::{self, Write},
+  iter::Peekable,
+  rc::Rc,
+  str::CharIndices,
+  thread,
 };
 
+use bigint::BigInt;
+use builtins::Builtin;
+use complex::Complex;
+use decimal::{Decimal, MAX_PRECISION, Rounding};
+use env::{Env, Function, Mode};
+use error::{Diagnostic, EvalError, ParseError};
+use value::Value;
+
+/// Stack size of the thread running the REPL. The parser and evaluator
+/// recurse, and their nesting limits are chosen to fit well within this.
+const STACK_SIZE: usize = 256 << 20;
+
 fn main() {
+  let repl = thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(repl)
+    .expect("failed to start the REPL thread");
+  if repl.join().is_err() {
+    std::process::exit(101);
+  }
+}
+
+fn repl() {
   let mut stdout = io::stdout();
   let stdin = io::stdin();
+  let mut env = Env::new();
+  for arg in std::env::args().skip(1) {
+    if arg == "--physics" {
+      env.enable_physics();
+    } else if arg == "--bignum" {
+      env.set_mode(Mode::Bignum);
+    } else if arg == "--rational" {
+      env.set_mode(Mode::Rational);
+    } else if arg == "--decimal" {
+      env.set_mode(Mode::Decimal);
+    } else if let Some(mode) = arg.strip_prefix("--mode=") {
+      match Mode::parse(mode) {
+        Some(mode) => env.set_mode(mode),
+        None => {
+          eprintln!("invalid --mode value: {:?}", mode);
+          std::process::exit(2);
+        }
+      }
+    } else if let Some(precision) = arg.strip_prefix("--precision=") {
+      match precision.parse() {
+        Ok(precision) if precision <= MAX_PRECISION => env.context_mut().precision = precision,
+        _ => {
+          eprintln!(
+            "invalid --precision value: {:?}, expected at most {}",
+            precision, MAX_PRECISION
+          );
+          std::process::exit(2);
+        }
+      }
+    } else if let Some(rounding) = arg.strip_prefix("--rounding=") {
+      match Rounding::parse(rounding) {
+        Some(rounding) => env.context_mut().rounding = rounding,
+        None => {
+          eprintln!("invalid --rounding value: {:?}", rounding);
+          std::process::exit(2);
+        }
+      }
+    } else if let Some(depth) = arg.strip_prefix("--max-depth=") {
+      match depth.parse() {
+        Ok(depth) => env.set_max_depth(depth),
+        Err(_) => {
+          eprintln!("invalid --max-depth value: {:?}", depth);
+          std::process::exit(2);
+        }
+      }
+    } else {
+      eprintln!("unknown argument: {:?}", arg);
+      std::process::exit(2);
+    }
+  }
+
   loop {
     print!(">> ");
     stdout.flush().unwrap();
 
-    let line = {
-      let mut buffer = String::new();
-      stdin.read_line(&mut buffer).unwrap();
-      buffer
-    };
+    let mut line = String::new();
+    match stdin.read_line(&mut line) {
+      // End of input, e.g. Ctrl-D or the end of a piped script.
+      Ok(0) => break,
+      Ok(_) => {}
+      Err(err) => {
+        println!("error: {}", err);
+        continue;
+      }
+    }
+    let line = line.trim_end();
 
-    if line.trim() == "exit" {
-      break;
+    match line.trim() {
+      "exit" => break,
+      "" => continue,
+      _ => {}
     }
 
-    let expr = Expr::from_str(&line);
+    if let Some(command) = line.trim().strip_prefix(':') {
+      match run_command(command, &mut env) {
+        Ok(output) => println!("{}", output),
+        Err(message) => println!("error: {}", message),
+      }
+      continue;
+    }
 
-    println!("{}", expr.eval());
+    match run(line, &mut env) {
+      Ok(output) => println!("{}", output),
+      Err(report) => println!("{}", report),
+    }
+  }
+}
+
+/// Evaluates one REPL line, returning the text to print.
+fn run(line: &str, env: &mut Env) -> Result<String, String> {
+  let expr = Expr::from_str(line).map_err(|err| err.render(line))?;
+
+  if let Some((name, params, body)) = expr.as_definition() {
+    env
+      .define(name, params.clone(), body.clone())
+      .map_err(|err| err.at(expr.span()).render(line))?;
+    return Ok(format!("{}({}) defined", name, params.join(", ")));
+  }
+
+  let value = expr.eval(env).map_err(|err| err.render(line))?;
+  let index = env.record(value.clone());
+  match value {
+    Value::Ratio(r) if env.approximate() && !r.is_integer() => {
+      Ok(format!("${} = {} ≈ {}", index, r, Value::Float(r.to_f64())))
+    }
+    _ => Ok(format!("${} = {}", index, value)),
   }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Runs a `:command` that changes REPL settings rather than evaluating.
+fn run_command(command: &str, env: &mut Env) -> Result<String, String> {
+  let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+  match (name, arg.trim()) {
+    ("approx", "") => {
+      let state = if env.toggle_approximate() {
+        "on"
+      } else {
+        "off"
+      };
+      Ok(format!("decimal approximations {}", state))
+    }
+    ("mode", "") => Ok(format!("mode {}", env.mode())),
+    ("mode", mode) => {
+      let mode = Mode::parse(mode).ok_or_else(|| {
+        format!(
+          "unknown mode {:?}, expected int, float, bignum, rational or decimal",
+          mode
+        )
+      })?;
+      env.set_mode(mode);
+      Ok(format!("mode {}", mode))
+    }
+    ("precision", "") => Ok(format!("precision {}", env.context().precision)),
+    ("precision", precision) => {
+      let precision = precision
+        .parse()
+        .ok()
+        .filter(|&precision| precision <= MAX_PRECISION)
+        .ok_or_else(|| {
+          format!(
+            "invalid precision {:?}, expected at most {}",
+            precision, MAX_PRECISION
+          )
+        })?;
+      env.context_mut().precision = precision;
+      Ok(format!("precision {}", precision))
+    }
+    ("rounding", "") => Ok(format!("rounding {}", env.context().rounding)),
+    ("rounding", rounding) => {
+      let rounding = Rounding::parse(rounding).ok_or_else(|| {
+        format!(
+          "unknown rounding mode {:?}, expected half-even, half-up or truncate",
+          rounding
+        )
+      })?;
+      env.context_mut().rounding = rounding;
+      Ok(format!("rounding {}", rounding))
+    }
+    _ => Err(format!("unknown command :{}", name)),
+  }
+}
+
+/// Byte offsets of a token or expression in the input line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+  start: usize,
+  end: usize,
+}
+
+impl Span {
+  fn new(start: usize, end: usize) -> Self {
+    Self { start, end }
+  }
+
+  fn join(self, other: Span) -> Span {
+    Span::new(self.start.min(other.start), self.end.max(other.end))
+  }
+}
+
+#[derive(Debug, Clone)]
 enum Token {
-  Atom(char),
-  Op(char),
+  Atom(Value),
+  Ident(String),
+  Op(&'static str),
+  Postfix(&'static str),
+  Arrow,
   Eof,
 }
 
+impl fmt::Display for Token {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Token::Atom(n) => write!(f, "{}", n),
+      Token::Ident(name) => write!(f, "{}", name),
+      Token::Op(c) | Token::Postfix(c) => write!(f, "'{}'", c),
+      Token::Arrow => write!(f, "'=>'"),
+      Token::Eof => write!(f, "end of input"),
+    }
+  }
+}
+
+/// Operators and punctuation, longest first so that `**` wins over `*`.
+const OPERATORS: &[&str] = &[
+  "**", "//", "=>", "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "^", "!",
+  "%", "=", "(", ")", "[", "]", ",",
+];
+
+/// How deeply expressions may nest, so that input like `((((…))))` reports
+/// an error instead of overflowing the stack of the recursive parser.
+const MAX_PARSE_NESTING: usize = 256;
+
 #[derive(Debug)]
 struct Lexer {
-  tokens: Vec<Token>,
+  tokens: Vec<(Token, Span)>,
+  eof: Span,
+  /// The number of `parse_expr` calls in progress.
+  depth: usize,
 }
 
 impl Lexer {
-  fn new(input: &str) -> Self {
-    let mut tokens: Vec<Token> = input
-      .chars()
-      .filter(|c| !c.is_ascii_whitespace())
-      .map(|c| match c {
-        '0'..='9' => Token::Atom(c),
-        '+' | '-' | '*' | '/' => Token::Op(c),
-        _ => panic!("Syntax error"),
-      })
-      .collect();
+  fn new(input: &str) -> Result<Self, Diagnostic<ParseError>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+      let token = match c {
+        c if c.is_ascii_whitespace() => {
+          chars.next();
+          continue;
+        }
+        '0'..='9' | '.' => Self::number(&mut chars)
+          .map(|value| Self::imaginary(&mut chars, value))
+          .map(Token::Atom),
+        c if c.is_ascii_alphabetic() || c == '_' => Ok(Self::word(&mut chars)),
+        '$' => Self::history_ref(&mut chars),
+        _ => match OPERATORS.iter().find(|op| input[start..].starts_with(*op)) {
+          Some(&op) => {
+            for _ in op.chars() {
+              chars.next();
+            }
+            Ok(match op {
+              "**" => Token::Op("^"),
+              "=>" => Token::Arrow,
+              "!" | "%" => Token::Postfix(op),
+              _ => Token::Op(op),
+            })
+          }
+          None => {
+            chars.next();
+            Err(ParseError::UnexpectedChar(c))
+          }
+        },
+      };
+
+      let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+      let span = Span::new(start, end);
+      tokens.push((token.map_err(|err| err.at(span))?, span));
+    }
 
     tokens.reverse();
 
-    Self { tokens }
+    Ok(Self {
+      tokens,
+      eof: Span::new(input.len(), input.len()),
+      depth: 0,
+    })
+  }
+
+  /// Lexes a number literal: an integer when it has no fraction or
+  /// exponent and fits in an `i64`, an exact decimal otherwise so that no
+  /// digits are lost before [`Env::literal`] picks the representation.
+  /// Exponents too large to expand give a float.
+  fn number(chars: &mut Peekable<CharIndices>) -> Result<Value, ParseError> {
+    if let Some(value) = Self::radix_number(chars) {
+      return value;
+    }
+
+    let mut literal = String::new();
+    Self::digits(chars, 10, &mut literal);
+    if chars.next_if(|&(_, c)| c == '.').is_some() {
+      literal.push('.');
+      Self::digits(chars, 10, &mut literal);
+    }
+
+    if let Some(exponent) = Self::exponent(chars) {
+      literal.push_str(&exponent);
+    }
+
+    if let Ok(n) = literal.parse() {
+      return Ok(Value::Int(n));
+    }
+    if let Some(n) = BigInt::parse_radix(&literal, 10) {
+      return Ok(Value::Big(n));
+    }
+    if let Some(d) = Decimal::parse(&literal) {
+      return Ok(Value::Decimal(d));
+    }
+    literal
+      .parse()
+      .map(Value::Float)
+      .map_err(|_| ParseError::InvalidNumber(literal))
+  }
+
+  /// Turns a number directly followed by `i`, like `3i`, into an
+  /// imaginary literal.
+  fn imaginary(chars: &mut Peekable<CharIndices>, value: Value) -> Value {
+    let mut lookahead = chars.clone();
+    if lookahead.next().is_none_or(|(_, c)| c != 'i')
+      || lookahead
+        .peek()
+        .is_some_and(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+    {
+      return value;
+    }
+
+    *chars = lookahead;
+    let im = value.as_float().expect("number literals are real");
+    Value::Complex(Complex::new(0.0, im))
+  }
+
+  /// Consumes a run of digits in `radix` into `out`. A `_` or `'` digit
+  /// group separator is skipped when it sits between two digits.
+  fn digits(chars: &mut Peekable<CharIndices>, radix: u32, out: &mut String) {
+    let mut seen_digit = false;
+
+    while let Some(&(_, c)) = chars.peek() {
+      if c.is_digit(radix) {
+        out.push(c);
+        seen_digit = true;
+      } else if matches!(c, '_' | '\'') && seen_digit {
+        let mut lookahead = chars.clone();
+        lookahead.next();
+        if !lookahead.peek().is_some_and(|(_, c)| c.is_digit(radix)) {
+          break;
+        }
+      } else {
+        break;
+      }
+      chars.next();
+    }
+  }
+
+  /// Consumes a `0x`, `0b` or `0o` prefixed integer literal, leaving
+  /// `chars` untouched when there is no such prefix.
+  fn radix_number(chars: &mut Peekable<CharIndices>) -> Option<Result<Value, ParseError>> {
+    let mut lookahead = chars.clone();
+
+    if !matches!(lookahead.next(), Some((_, '0'))) {
+      return None;
+    }
+    let radix = match lookahead.next() {
+      Some((_, 'x' | 'X')) => 16,
+      Some((_, 'b' | 'B')) => 2,
+      Some((_, 'o' | 'O')) => 8,
+      _ => return None,
+    };
+
+    let mut digits = String::new();
+    Self::digits(&mut lookahead, radix, &mut digits);
+
+    if digits.is_empty() {
+      return None;
+    }
+
+    *chars = lookahead;
+    let value = match i64::from_str_radix(&digits, radix) {
+      Ok(n) => Ok(Value::Int(n)),
+      Err(_) => BigInt::parse_radix(&digits, radix)
+        .map(Value::Big)
+        .ok_or(ParseError::InvalidNumber(digits)),
+    };
+    Some(value)
+  }
+
+  /// Consumes an exponent suffix like `e-9` or `E23`, leaving `chars`
+  /// untouched when the `e` is not followed by digits.
+  fn exponent(chars: &mut Peekable<CharIndices>) -> Option<String> {
+    let mut lookahead = chars.clone();
+    let mut exponent = String::new();
+
+    match lookahead.next() {
+      Some((_, c @ ('e' | 'E'))) => exponent.push(c),
+      _ => return None,
+    }
+    if let Some(&(_, c @ ('+' | '-'))) = lookahead.peek() {
+      exponent.push(c);
+      lookahead.next();
+    }
+    Self::digits(&mut lookahead, 10, &mut exponent);
+
+    if !exponent.ends_with(|c: char| c.is_ascii_digit()) {
+      return None;
+    }
+
+    *chars = lookahead;
+    Some(exponent)
+  }
+
+  fn word(chars: &mut Peekable<CharIndices>) -> Token {
+    let mut word = String::new();
+
+    while let Some(&(_, c)) = chars.peek() {
+      if !c.is_ascii_alphanumeric() && c != '_' {
+        break;
+      }
+      word.push(c);
+      chars.next();
+    }
+
+    match word.as_str() {
+      "inf" => Token::Atom(Value::Float(f64::INFINITY)),
+      "nan" => Token::Atom(Value::Float(f64::NAN)),
+      "true" => Token::Atom(Value::Bool(true)),
+      "false" => Token::Atom(Value::Bool(false)),
+      "and" => Token::Op("&&"),
+      "or" => Token::Op("||"),
+      "not" => Token::Op("not"),
+
+      _ => Token::Ident(word),
+    }
+  }
+
+  /// Lexes a `$n` reference to the n-th REPL result as an identifier.
+  fn history_ref(chars: &mut Peekable<CharIndices>) -> Result<Token, ParseError> {
+    chars.next();
+
+    let mut name = String::from("$");
+    Self::digits(chars, 10, &mut name);
+
+    match name.len() {
+      1 => Err(ParseError::UnexpectedChar('$')),
+      _ => Ok(Token::Ident(name)),
+    }
+  }
+
+  /// Checks whether the tokens after a `(` form a lambda parameter list
+  /// like `(x, y) =>`.
+  fn lambda_ahead(&self) -> bool {
+    let mut tokens = self.tokens.iter().rev().map(|(token, _)| token);
+
+    if let Some(Token::Op(")")) = tokens.clone().next() {
+      return matches!(tokens.nth(1), Some(Token::Arrow));
+    }
+    loop {
+      match (tokens.next(), tokens.next()) {
+        (Some(Token::Ident(_)), Some(Token::Op(","))) => continue,
+        (Some(Token::Ident(_)), Some(Token::Op(")"))) => {
+          return matches!(tokens.next(), Some(Token::Arrow));
+        }
+        _ => return false,
+      }
+    }
+  }
+
+  /// Checks whether the token after the next one can start an operand. A
+  /// sign only counts when nothing separates it from its operand, so
+  /// `10 % -3` is modulo while `50% - 3` subtracts from a percentage.
+  fn operand_after_peek(&self) -> bool {
+    let ahead = |n: usize| self.tokens.len().checked_sub(n).map(|i| &self.tokens[i]);
+    let starts_operand = |token: &Token| {
+      matches!(
+        token,
+        Token::Atom(_) | Token::Ident(_) | Token::Op("(" | "[")
+      )
+    };
+    match ahead(2) {
+      Some((Token::Op("+" | "-"), sign)) => {
+        ahead(3).is_some_and(|(token, operand)| starts_operand(token) && operand.start == sign.end)
+      }
+      Some((token, _)) => starts_operand(token),
+      None => false,
+    }
   }
 
-  fn next(&mut self) -> Token {
-    self.tokens.pop().unwrap_or(Token::Eof)
+  fn next(&mut self) -> (Token, Span) {
+    self.tokens.pop().unwrap_or((Token::Eof, self.eof))
   }
 
-  fn peek(&self) -> Token {
-    self.tokens.last().copied().unwrap_or(Token::Eof)
+  fn peek(&self) -> (Token, Span) {
+    self
+      .tokens
+      .last()
+      .cloned()
+      .unwrap_or((Token::Eof, self.eof))
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Expr {
-  Atom(char),
-  Op(char, Vec<Expr>),
+  Atom(Value, Span),
+  Var(String, Span),
+  Call(String, Vec<Expr>, Span),
+  List(Vec<Expr>, Span),
+  Lambda(Vec<String>, Box<Expr>, Span),
+  Op(&'static str, Vec<Expr>, Span),
 }
 
 impl fmt::Display for Expr {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      Expr::Atom(i) => write!(f, "{}", i),
-      Expr::Op(head, rest) => {
+      Expr::Atom(i, _) => write!(f, "{}", i),
+      Expr::Var(name, _) => write!(f, "{}", name),
+      Expr::Call(name, args, _) => {
+        write!(f, "({}", name)?;
+        for s in args {
+          write!(f, " {}", s)?
+        }
+        write!(f, ")")
+      }
+      Expr::List(items, _) => {
+        write!(f, "[")?;
+        for (i, s) in items.iter().enumerate() {
+          if i > 0 {
+            write!(f, " ")?;
+          }
+          write!(f, "{}", s)?;
+        }
+        write!(f, "]")
+      }
+      Expr::Lambda(params, body, _) => write!(f, "(=> ({}) {})", params.join(" "), body),
+      Expr::Op(head, rest, _) => {
         write!(f, "({}", head)?;
         for s in rest {
           write!(f, " {}", s)?
@@ -86,25 +575,109 @@ impl fmt::Display for Expr {
 }
 
 impl Expr {
-  fn from_str(input: &str) -> Self {
-    let mut lexer = Lexer::new(input);
-    Expr::parse_expr(&mut lexer, 0.0)
+  fn from_str(input: &str) -> Result<Self, Diagnostic<ParseError>> {
+    let mut lexer = Lexer::new(input)?;
+    let expr = Expr::parse_expr(&mut lexer, 0.0)?;
+
+    match lexer.next() {
+      (Token::Eof, _) => Ok(expr),
+      (Token::Op(")"), span) => Err(ParseError::UnexpectedCloseParen.at(span)),
+      (t, span) => Err(ParseError::UnexpectedToken(t).at(span)),
+    }
   }
 
-  fn parse_expr(lexer: &mut Lexer, min_bp: f32) -> Self {
-    let mut lhs = match lexer.next() {
-      Token::Atom(c) => Expr::Atom(c),
-      t => panic!("bad token: {:?}", t),
+  fn parse_expr(lexer: &mut Lexer, min_bp: f32) -> Result<Self, Diagnostic<ParseError>> {
+    // Errors abandon the whole parse, so only the successful return below
+    // needs to give the level back.
+    lexer.depth += 1;
+    if lexer.depth > MAX_PARSE_NESTING {
+      return Err(ParseError::TooDeeplyNested.at(lexer.peek().1));
+    }
+
+    let (token, span) = lexer.next();
+    let mut lhs = match token {
+      Token::Atom(n) => Expr::Atom(n, span),
+      Token::Ident(name) => match lexer.peek() {
+        (Token::Op("("), _) => {
+          let (_, open) = lexer.next();
+          let (args, close) = Self::parse_list(lexer, open, ")")?;
+          Expr::Call(name, args, span.join(close))
+        }
+        (Token::Arrow, _) => {
+          if name.starts_with('$') {
+            return Err(ParseError::InvalidLambdaParams.at(span));
+          }
+          lexer.next();
+          Self::parse_lambda(lexer, vec![name], span)?
+        }
+        _ => Expr::Var(name, span),
+      },
+      Token::Op("(") if lexer.lambda_ahead() => {
+        let mut params = Vec::new();
+        while let (Token::Ident(name), param) = lexer.next() {
+          if name.starts_with('$') {
+            return Err(ParseError::InvalidLambdaParams.at(param));
+          }
+          params.push(name);
+          if let (Token::Op(")"), _) = lexer.next() {
+            break;
+          }
+        }
+        lexer.next();
+        Self::parse_lambda(lexer, params, span)?
+      }
+      Token::Op("[") => {
+        let (items, close) = Self::parse_list(lexer, span, "]")?;
+        Expr::List(items, span.join(close))
+      }
+      Token::Op("(") => {
+        let lhs = Self::parse_expr(lexer, 0.0)?;
+        match lexer.next() {
+          (Token::Op(")"), _) => lhs,
+          (Token::Eof, _) => return Err(ParseError::MissingCloseParen.at(span)),
+          (t, span) => return Err(ParseError::UnexpectedToken(t).at(span)),
+        }
+      }
+      Token::Eof => return Err(ParseError::UnexpectedEof.at(span)),
+      Token::Op(op) | Token::Postfix(op) => {
+        let ((), r_bp) = Self::prefix_binding_power(op)
+          .ok_or_else(|| ParseError::UnexpectedToken(token.clone()).at(span))?;
+        // Before an operand, `!` is logical not rather than factorial.
+        let op = if op == "!" { "not" } else { op };
+        let rhs = Self::parse_expr(lexer, r_bp)?;
+        let span = span.join(rhs.span());
+        Expr::Op(op, vec![rhs], span)
+      }
+      t => return Err(ParseError::UnexpectedToken(t).at(span)),
     };
 
     loop {
-      let op = match lexer.peek() {
-        Token::Eof => break,
+      let (token, span) = lexer.peek();
+      let op = match token {
+        Token::Op("=") if !lhs.is_assign_target() => {
+          return Err(ParseError::InvalidAssignTarget.at(lhs.span()));
+        }
+        Token::Eof | Token::Op(")" | "]" | ",") => break,
         Token::Op(op) => op,
-        _ => panic!("Bad token"),
+        // `%` is modulo rather than percent when an operand follows it.
+        Token::Postfix("%") if lexer.operand_after_peek() => "%",
+        Token::Postfix(op) => {
+          let (l_bp, ()) = Self::postfix_binding_power(op)
+            .ok_or_else(|| ParseError::UnexpectedToken(token.clone()).at(span))?;
+          if l_bp < min_bp {
+            break;
+          }
+          lexer.next();
+
+          let span = lhs.span().join(span);
+          lhs = Expr::Op(op, vec![lhs], span);
+          continue;
+        }
+        _ => return Err(ParseError::UnexpectedToken(token).at(span)),
       };
 
-      let (l_bp, r_bp) = Self::infix_binding_power(op);
+      let (l_bp, r_bp) = Self::infix_binding_power(op)
+        .ok_or_else(|| ParseError::UnexpectedToken(token.clone()).at(span))?;
 
       if l_bp < min_bp {
         break;
@@ -112,38 +685,403 @@ impl Expr {
 
       lexer.next();
 
-      let rhs = Self::parse_expr(lexer, r_bp);
-      lhs = Expr::Op(op, vec![lhs, rhs]);
+      let rhs = Self::parse_expr(lexer, r_bp)?;
+      let span = lhs.span().join(rhs.span());
+      lhs = Expr::Op(op, vec![lhs, rhs], span);
     }
 
-    lhs
+    lexer.depth -= 1;
+    Ok(lhs)
   }
 
-  fn infix_binding_power(op: char) -> (f32, f32) {
+  /// Parses comma separated expressions after the `open` delimiter up to
+  /// and including `close`, returning them with the span of `close`.
+  fn parse_list(
+    lexer: &mut Lexer,
+    open: Span,
+    close: &str,
+  ) -> Result<(Vec<Self>, Span), Diagnostic<ParseError>> {
+    let mut items = Vec::new();
+
+    if let (Token::Op(c), end) = lexer.peek()
+      && c == close
+    {
+      lexer.next();
+      return Ok((items, end));
+    }
+
+    loop {
+      items.push(Self::parse_expr(lexer, 0.0)?);
+      match lexer.next() {
+        (Token::Op(","), _) => continue,
+        (Token::Op(c), end) if c == close => return Ok((items, end)),
+        (Token::Eof, _) if close == "]" => return Err(ParseError::MissingCloseBracket.at(open)),
+        (Token::Eof, _) => return Err(ParseError::MissingCloseParen.at(open)),
+        (t, span) => return Err(ParseError::UnexpectedToken(t).at(span)),
+      }
+    }
+  }
+
+  /// Parses the body of a lambda after its `=>`. The body extends as far
+  /// to the right as possible.
+  fn parse_lambda(
+    lexer: &mut Lexer,
+    params: Vec<String>,
+    span: Span,
+  ) -> Result<Self, Diagnostic<ParseError>> {
+    let body = Self::parse_expr(lexer, 0.0)?;
+    let span = span.join(body.span());
+    Ok(Expr::Lambda(params, Box::new(body), span))
+  }
+
+  fn prefix_binding_power(op: &str) -> Option<((), f32)> {
     match op {
-      '+' | '-' => (1.0, 1.1),
-      '*' | '/' => (2.0, 2.1),
-      _ => panic!("bad op: {:?}", op),
+      // The keyword negates a whole comparison, as in `not a == b`, while
+      // `!` binds as tightly as unary minus, as in C.
+      "not" => Some(((), 0.5)),
+      "+" | "-" | "!" => Some(((), 3.0)),
+      _ => None,
+    }
+  }
+
+  fn postfix_binding_power(op: &str) -> Option<(f32, ())> {
+    match op {
+      "!" | "%" => Some((5.0, ())),
+      _ => None,
+    }
+  }
+
+  fn infix_binding_power(op: &str) -> Option<(f32, f32)> {
+    match op {
+      "=" => Some((0.2, 0.1)),
+      "||" => Some((0.3, 0.4)),
+      "&&" => Some((0.4, 0.5)),
+      "==" | "!=" | "<" | "<=" | ">" | ">=" => Some((0.6, 0.7)),
+      "+" | "-" => Some((1.0, 1.1)),
+      "*" | "/" | "//" | "%" => Some((2.0, 2.1)),
+      "^" => Some((4.1, 4.0)),
+      _ => None,
+    }
+  }
+
+  /// Variables and `f(x, y)` function heads can appear left of `=`.
+  fn is_assign_target(&self) -> bool {
+    match self {
+      Expr::Var(name, _) => !name.starts_with('$'),
+      Expr::Call(_, params, _) => params
+        .iter()
+        .all(|param| matches!(param, Expr::Var(..)) && param.is_assign_target()),
+      _ => false,
+    }
+  }
+
+  /// Splits `f(x, y) = body` into its name, parameters and body.
+  fn as_definition(&self) -> Option<(&str, Vec<String>, &Expr)> {
+    let Expr::Op("=", operands, _) = self else {
+      return None;
+    };
+    let [Expr::Call(name, params, _), body] = operands.as_slice() else {
+      return None;
+    };
+
+    let params = params
+      .iter()
+      .filter_map(|param| match param {
+        Expr::Var(name, _) => Some(name.clone()),
+        _ => None,
+      })
+      .collect();
+    Some((name, params, body))
+  }
+
+  fn span(&self) -> Span {
+    match self {
+      Expr::Atom(_, span)
+      | Expr::Var(_, span)
+      | Expr::Call(_, _, span)
+      | Expr::List(_, span)
+      | Expr::Lambda(_, _, span)
+      | Expr::Op(_, _, span) => *span,
     }
   }
 
-  fn eval(&self) -> f32 {
+  fn eval(&self, env: &mut Env) -> Result<Value, Diagnostic<EvalError>> {
+    env.descend().map_err(|err| err.at(self.span()))?;
+    let value = self.eval_inner(env);
+    env.ascend();
+    value
+  }
+
+  fn eval_inner(&self, env: &mut Env) -> Result<Value, Diagnostic<EvalError>> {
     match self {
-      Expr::Atom(atom) => match atom {
-        '0'..='9' => atom.to_digit(10).unwrap() as f32,
-        _ => unreachable!(),
+      Expr::Atom(atom, _) => Ok(env.literal(atom.clone())),
+      Expr::Var(name, span) => {
+        Self::resolve(name, env).ok_or_else(|| EvalError::UnknownVariable(name.clone()).at(*span))
+      }
+      Expr::Call(name, args, span) => {
+        // Named functions take precedence over variables holding one.
+        let callee = match env.function(name) {
+          Some(function) => Value::Func(function),
+          None if Builtin::lookup(name).is_some() => Value::Builtin(name.clone()),
+          None => env
+            .get(name)
+            .ok_or_else(|| EvalError::UnknownFunction(name.clone()).at(*span))?,
+        };
+
+        let args = args
+          .iter()
+          .map(|arg| arg.eval(env))
+          .collect::<Result<Vec<_>, _>>()?;
+        callee.call(args, env).map_err(|err| err.at(*span))
+      }
+      Expr::List(items, _) => items
+        .iter()
+        .map(|item| item.eval(env))
+        .collect::<Result<_, _>>()
+        .map(Value::List),
+      Expr::Lambda(params, body, span) => {
+        env.check_params(params).map_err(|err| err.at(*span))?;
+        Ok(Value::Func(Rc::new(Function {
+          name: "lambda".to_string(),
+          params: params.clone(),
+          body: (**body).clone(),
+          captured: env.locals(),
+        })))
+      }
+      Expr::Op("=", operands, span) => match operands.as_slice() {
+        [Expr::Var(name, target), rhs] => {
+          let value = rhs.eval(env)?;
+          env
+            .set(name, value.clone())
+            .map_err(|err| err.at(*target))?;
+          Ok(value)
+        }
+        [Expr::Call(..), _] => Err(EvalError::NestedDefinition.at(*span)),
+        _ => unreachable!("assignment target is checked by the parser"),
       },
-      Expr::Op(op, operands) => {
-        let lhs = operands.first().unwrap().eval();
-        let rhs = operands.last().unwrap().eval();
-        match op {
-          '+' => lhs + rhs,
-          '-' => lhs - rhs,
-          '*' => lhs * rhs,
-          '/' => lhs / rhs,
-          _ => panic!("Unsupported operator"),
+      Expr::Op(op @ ("&&" | "||"), operands, _) => {
+        let [lhs, rhs] = operands.as_slice() else {
+          unreachable!("logical operators are binary");
+        };
+        let lhs_value = lhs.eval(env)?;
+        // `a || b` is decided by a true `a` and `a && b` by a false one, in
+        // which case `b` is never evaluated.
+        let short_circuit = *op == "||";
+        if lhs_value.as_bool().map_err(|err| err.at(lhs.span()))? == short_circuit {
+          return Ok(lhs_value);
         }
+        let rhs_value = rhs.eval(env)?;
+        rhs_value.as_bool().map_err(|err| err.at(rhs.span()))?;
+        Ok(rhs_value)
       }
+      Expr::Op(op, operands, span) => match operands.as_slice() {
+        [operand] => {
+          let value = operand.eval(env)?;
+          Value::unary(op, &value).map_err(|err| err.at(operand.span()))
+        }
+        [lhs, rhs] => {
+          let lhs_value = lhs.eval(env)?;
+          let rhs_value = match (*op, rhs) {
+            // Desk calculator semantics: `200 + 10%` adds 10% of 200.
+            ("+" | "-", Expr::Op("%", percent, _)) if percent.len() == 1 => {
+              Value::binary("*", &lhs_value, &rhs.eval(env)?, env.context())
+                .map_err(|err| err.at(*span))?
+            }
+            _ => rhs.eval(env)?,
+          };
+
+          Value::binary(op, &lhs_value, &rhs_value, env.context()).map_err(|err| {
+            // Point at the offending operand where there is one.
+            let span = match err {
+              EvalError::DivisionByZero => rhs.span(),
+              EvalError::TypeMismatch { .. } if lhs_value.as_float().is_err() => lhs.span(),
+              EvalError::TypeMismatch { .. } => rhs.span(),
+              _ => *span,
+            };
+            err.at(span)
+          })
+        }
+        _ => Err(EvalError::UnknownOperator(op).at(*span)),
+      },
+    }
+  }
+
+  /// Resolves a name to a variable, or failing that to a function value.
+  fn resolve(name: &str, env: &Env) -> Option<Value> {
+    if let Some(value) = env.get(name) {
+      return Some(value);
     }
+    if let Some(function) = env.function(name) {
+      return Some(Value::Func(function));
+    }
+    Builtin::lookup(name).map(|_| Value::Builtin(name.to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(input: &str) -> String {
+    Expr::from_str(input).unwrap().to_string()
+  }
+
+  /// Evaluates `input` in a fresh environment in `mode`, returning the
+  /// value as the REPL prints it.
+  fn eval_in(mode: Mode, input: &str) -> String {
+    let mut env = Env::new();
+    env.set_mode(mode);
+    let output = run(input, &mut env).unwrap();
+    let (_, value) = output.split_once(" = ").unwrap();
+    value.to_string()
+  }
+
+  fn eval(input: &str) -> String {
+    eval_in(Mode::Int, input)
+  }
+
+  #[test]
+  fn precedence_and_associativity() {
+    assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+    assert_eq!(parse("1 - 2 - 3"), "(- (- 1 2) 3)");
+    assert_eq!(parse("2 ^ 3 ^ 2"), "(^ 2 (^ 3 2))");
+    assert_eq!(parse("-2 ^ 2"), "(- (^ 2 2))");
+    assert_eq!(parse("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+    assert_eq!(parse("x = y = 1"), "(= x (= y 1))");
+  }
+
+  #[test]
+  fn percent_or_modulo() {
+    assert_eq!(parse("50% + 3"), "(+ (% 50) 3)");
+    assert_eq!(parse("200 + 10% - 5"), "(- (+ 200 (% 10)) 5)");
+    assert_eq!(parse("10 % 3"), "(% 10 3)");
+    assert_eq!(parse("10 % -3"), "(% 10 (- 3))");
+    assert_eq!(parse("10 % - 3"), "(- (% 10) 3)");
+
+    assert_eq!(eval("50% + 3"), "3.5");
+    assert_eq!(eval("200 + 10% - 5"), "215.0");
+    assert_eq!(eval("10 % 3"), "1");
+    assert_eq!(eval("10 % -3"), "-2");
+  }
+
+  #[test]
+  fn factorial_and_logical_not() {
+    assert_eq!(parse("3! + 1"), "(+ (! 3) 1)");
+    assert_eq!(parse("!a == b"), "(== (not a) b)");
+    assert_eq!(parse("not a == b"), "(not (== a b))");
+    assert_eq!(parse("a || b && c"), "(|| a (&& b c))");
+  }
+
+  #[test]
+  fn calls_lists_and_lambdas() {
+    assert_eq!(parse("max(1, 2 + 3)"), "(max 1 (+ 2 3))");
+    assert_eq!(parse("[1, [2]]"), "[1 [2]]");
+    assert_eq!(parse("(x, y) => x * y"), "(=> (x y) (* x y))");
+    assert_eq!(parse("map(x => x + 1, [])"), "(map (=> (x) (+ x 1)) [])");
+  }
+
+  #[test]
+  fn decimal_rounding_is_exact() {
+    let decimal = |input| eval_in(Mode::Decimal, input);
+    assert_eq!(decimal("floor(12345678901234567.5)"), "12345678901234567");
+    assert_eq!(decimal("ceil(-12345678901234567.5)"), "-12345678901234567");
+    assert_eq!(decimal("round(2.5)"), "2");
+    assert_eq!(decimal("round(-3.5)"), "-4");
+    assert_eq!(eval("round(2.5)"), "3");
+  }
+
+  #[test]
+  fn decimal_factorial_needs_an_integer() {
+    let mut env = Env::new();
+    env.set_mode(Mode::Decimal);
+    let error = run("5.00000000000000000001!", &mut env).unwrap_err();
+    assert!(
+      error.starts_with("error: operator '!' is not defined for 5"),
+      "{}",
+      error
+    );
+    assert_eq!(eval_in(Mode::Decimal, "5.0!"), "120");
+  }
+
+  #[test]
+  fn rational_factorial_needs_an_integer() {
+    let mut env = Env::new();
+    env.set_mode(Mode::Rational);
+    let error = run("(5 + 1/10^20)!", &mut env).unwrap_err();
+    assert!(
+      error.starts_with("error: operator '!' is not defined for 5"),
+      "{}",
+      error
+    );
+    assert_eq!(eval_in(Mode::Rational, "(10/2)!"), "120");
+  }
+
+  #[test]
+  fn rational_rounding_is_exact() {
+    let rational = |input| eval_in(Mode::Rational, input);
+    assert_eq!(rational("floor(12345678901234567/2)"), "6172839450617283");
+    assert_eq!(rational("floor(2^100/3)"), "422550200076076467165567735125");
+    assert_eq!(rational("ceil(-7/2)"), "-3");
+    assert_eq!(rational("round(5/2)"), "3");
+    assert_eq!(rational("round(-5/2)"), "-3");
+    assert_eq!(rational("round(-7/3)"), "-2");
+  }
+
+  #[test]
+  fn integer_division_reports_overflow() {
+    assert_eq!(eval("7 / 2"), "3.5");
+    assert_eq!(eval("-6 / 3"), "-2");
+    for input in [
+      "(-9223372036854775807 - 1) / -1",
+      "(-9223372036854775807 - 1) // -1",
+    ] {
+      let error = run(input, &mut Env::new()).unwrap_err();
+      assert!(error.starts_with("error: integer overflow"), "{}", error);
+    }
+  }
+
+  #[test]
+  fn bignum_results_are_bounded() {
+    let mut env = Env::new();
+    env.set_mode(Mode::Bignum);
+    run("x = 2^32000", &mut env).unwrap();
+    assert!(run("x * x", &mut env).is_ok());
+    let error = run("x * x * x", &mut env).unwrap_err();
+    assert!(error.starts_with("error: integer overflow"), "{}", error);
+  }
+
+  #[test]
+  fn long_operator_chains_evaluate() {
+    let sum = thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(|| eval(&vec!["1"; 6000].join(" + ")))
+      .unwrap()
+      .join()
+      .unwrap();
+    assert_eq!(sum, "6000");
+  }
+
+  #[test]
+  fn parse_errors() {
+    let error = |input: &str| Expr::from_str(input).unwrap_err().error.to_string();
+    assert_eq!(error("(1 + 2"), "unbalanced parentheses: missing ')'");
+    assert_eq!(error("1 + 2)"), "unbalanced parentheses: unexpected ')'");
+    assert_eq!(error("1 +"), "unexpected end of input");
+    assert_eq!(error("1 + 2 = 3"), "can only assign to a variable");
+  }
+
+  #[test]
+  fn nesting_limit() {
+    // Test threads have a small stack, so parse on one sized like the REPL's.
+    let error = thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(|| {
+        let nested = format!("{}1{}", "(".repeat(300), ")".repeat(300));
+        Expr::from_str(&nested).unwrap_err().error.to_string()
+      })
+      .unwrap()
+      .join()
+      .unwrap();
+    assert_eq!(error, "expression is nested too deeply");
   }
 }
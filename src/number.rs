@@ -0,0 +1,418 @@
+use std::cmp::Ordering;
+
+use crate::{
+  bigint::BigInt,
+  complex::Complex,
+  decimal::{Context, Decimal},
+  error::EvalError,
+  rational::Rational,
+  value::Value,
+};
+
+/// Largest bignum result in bits, so that a typo like `9^9^9` reports an
+/// error instead of exhausting memory. Printing and dividing bignums take
+/// time quadratic in their size, so this also keeps each result to a fraction
+/// of a second rather than minutes.
+pub const MAX_BIG_BITS: u64 = 1 << 16;
+
+/// Arithmetic for one numeric representation. Operations return `Ok(None)`
+/// when the exact result can't be represented, e.g. `1 / 2` for integers,
+/// so the caller can fall back to floats.
+pub trait Number: Sized {
+  /// Converts an operand, or returns `None` if it has no exact
+  /// representation as `Self`.
+  fn from_value(value: &Value) -> Option<Self>;
+
+  fn into_value(self) -> Value;
+
+  fn is_zero(&self) -> bool;
+
+  fn add(&self, other: &Self, context: Context) -> Result<Self, EvalError>;
+
+  fn sub(&self, other: &Self, context: Context) -> Result<Self, EvalError>;
+
+  fn mul(&self, other: &Self, context: Context) -> Result<Self, EvalError>;
+
+  /// Divides by a nonzero `other`.
+  fn div(&self, other: &Self, context: Context) -> Result<Option<Self>, EvalError>;
+
+  /// The quotient rounded towards negative infinity and the remainder, for
+  /// a nonzero `other`. `-7 // 2` is `-4` and `-7 % 2` is `1`.
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError>;
+
+  fn pow(&self, other: &Self, context: Context) -> Result<Option<Self>, EvalError>;
+
+  /// Orders two numbers, or returns `None` when they are unordered, like
+  /// NaN, or only support equality, like complex numbers.
+  fn compare(&self, other: &Self) -> Option<Ordering>;
+}
+
+/// Applies a binary operator in the representation `N`.
+pub fn apply<N: Number>(op: &str, a: &N, b: &N, context: Context) -> Result<Option<N>, EvalError> {
+  if matches!(op, "/" | "//" | "%") && b.is_zero() {
+    return Err(EvalError::DivisionByZero);
+  }
+  match op {
+    "+" => a.add(b, context).map(Some),
+    "-" => a.sub(b, context).map(Some),
+    "*" => a.mul(b, context).map(Some),
+    "/" => a.div(b, context),
+    "//" => Ok(a.div_mod_floor(b)?.map(|(quotient, _)| quotient)),
+    "%" => Ok(a.div_mod_floor(b)?.map(|(_, remainder)| remainder)),
+    "^" => a.pow(b, context),
+    _ => Ok(None),
+  }
+}
+
+/// Applies a comparison operator in the representation `N`. Unordered
+/// operands are unequal and neither less nor greater than each other.
+pub fn compare<N: Number>(op: &str, a: &N, b: &N) -> Option<bool> {
+  let ordering = a.compare(b);
+  let result = match op {
+    "==" => ordering == Some(Ordering::Equal),
+    "!=" => ordering != Some(Ordering::Equal),
+    "<" => ordering == Some(Ordering::Less),
+    "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+    ">" => ordering == Some(Ordering::Greater),
+    ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    _ => return None,
+  };
+  Some(result)
+}
+
+impl Number for i64 {
+  fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Int(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  fn into_value(self) -> Value {
+    Value::Int(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    *self == 0
+  }
+
+  fn add(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    self.checked_add(*other).ok_or(EvalError::Overflow)
+  }
+
+  fn sub(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    self.checked_sub(*other).ok_or(EvalError::Overflow)
+  }
+
+  fn mul(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    self.checked_mul(*other).ok_or(EvalError::Overflow)
+  }
+
+  fn div(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    // Only `i64::MIN / -1` fails here, as the divisor is never zero.
+    let quotient = self.checked_div(*other).ok_or(EvalError::Overflow)?;
+    Ok((quotient * other == *self).then_some(quotient))
+  }
+
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    let quotient = self.checked_div(*other).ok_or(EvalError::Overflow)?;
+    let remainder = self.wrapping_rem(*other);
+    if remainder != 0 && (remainder < 0) != (*other < 0) {
+      Ok(Some((quotient - 1, remainder + other)))
+    } else {
+      Ok(Some((quotient, remainder)))
+    }
+  }
+
+  fn pow(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    match u32::try_from(*other) {
+      Ok(exponent) => self
+        .checked_pow(exponent)
+        .map(Some)
+        .ok_or(EvalError::Overflow),
+      Err(_) => Ok(None),
+    }
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Number for f64 {
+  fn from_value(value: &Value) -> Option<Self> {
+    value.as_float().ok()
+  }
+
+  fn into_value(self) -> Value {
+    Value::Float(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    *self == 0.0
+  }
+
+  fn add(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self + other)
+  }
+
+  fn sub(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self - other)
+  }
+
+  fn mul(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self * other)
+  }
+
+  fn div(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    Ok(Some(self / other))
+  }
+
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    let quotient = (self / other).floor();
+    Ok(Some((quotient, self - other * quotient)))
+  }
+
+  fn pow(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    Ok(Some(self.powf(*other)))
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    self.partial_cmp(other)
+  }
+}
+
+/// Reports bignums larger than [`MAX_BIG_BITS`] as an overflow. Operands
+/// are within the limit too, so building the result first is cheap.
+fn bounded(n: BigInt) -> Result<BigInt, EvalError> {
+  if n.bits() > MAX_BIG_BITS {
+    Err(EvalError::Overflow)
+  } else {
+    Ok(n)
+  }
+}
+
+impl Number for BigInt {
+  fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Int(n) => Some(BigInt::from(*n)),
+      Value::Big(n) => Some(n.clone()),
+      _ => None,
+    }
+  }
+
+  fn into_value(self) -> Value {
+    Value::Big(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    self.is_zero()
+  }
+
+  fn add(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    bounded(self + other)
+  }
+
+  fn sub(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    bounded(self - other)
+  }
+
+  fn mul(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    bounded(self * other)
+  }
+
+  fn div(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    match self.div_rem(other) {
+      Some((quotient, remainder)) if remainder.is_zero() => Ok(Some(quotient)),
+      _ => Ok(None),
+    }
+  }
+
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    Ok(self.div_mod_floor(other))
+  }
+
+  fn pow(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    match other.to_i64().and_then(|b| u32::try_from(b).ok()) {
+      Some(exponent) if self.bits().saturating_mul(exponent as u64) <= MAX_BIG_BITS => {
+        Ok(Some(self.pow(exponent)))
+      }
+      None if other.is_negative() => Ok(None),
+      _ => Err(EvalError::Overflow),
+    }
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Number for Rational {
+  fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Ratio(r) => Some(r.clone()),
+      _ => BigInt::from_value(value).map(Rational::from),
+    }
+  }
+
+  fn into_value(self) -> Value {
+    Value::Ratio(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    self.is_zero()
+  }
+
+  fn add(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self + other)
+  }
+
+  fn sub(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self - other)
+  }
+
+  fn mul(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(self * other)
+  }
+
+  fn div(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    Ok(self.checked_div(other))
+  }
+
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    let quotient = self.checked_div(other).ok_or(EvalError::DivisionByZero)?;
+    let quotient = Rational::from(quotient.floor());
+    let remainder = self - &(other * &quotient);
+    Ok(Some((quotient, remainder)))
+  }
+
+  /// Only integer powers are exact.
+  fn pow(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    if !other.is_integer() {
+      return Ok(None);
+    }
+    let bits = self.numer().bits().max(self.denom().bits());
+    match other.numer().to_i64() {
+      Some(exponent) if bits.saturating_mul(exponent.unsigned_abs()) <= MAX_BIG_BITS => self
+        .pow(exponent)
+        .map(Some)
+        .ok_or(EvalError::DivisionByZero),
+      _ => Err(EvalError::Overflow),
+    }
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Number for Decimal {
+  fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Decimal(d) => Some(d.clone()),
+      _ => BigInt::from_value(value).map(Decimal::from),
+    }
+  }
+
+  fn into_value(self) -> Value {
+    Value::Decimal(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    self.is_zero()
+  }
+
+  fn add(&self, other: &Self, context: Context) -> Result<Self, EvalError> {
+    Ok(self.add(other, context))
+  }
+
+  fn sub(&self, other: &Self, context: Context) -> Result<Self, EvalError> {
+    Ok(self.sub(other, context))
+  }
+
+  fn mul(&self, other: &Self, context: Context) -> Result<Self, EvalError> {
+    Ok(self.mul(other, context))
+  }
+
+  fn div(&self, other: &Self, context: Context) -> Result<Option<Self>, EvalError> {
+    // `apply` has already ruled out a zero divisor.
+    self
+      .div(other, context)
+      .map(Some)
+      .ok_or(EvalError::Overflow)
+  }
+
+  fn div_mod_floor(&self, other: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    Ok(self.div_mod_floor(other))
+  }
+
+  /// Only integer powers are exact.
+  fn pow(&self, other: &Self, context: Context) -> Result<Option<Self>, EvalError> {
+    match other.to_integer().and_then(|b| b.to_i64()) {
+      Some(exponent) if self.bits().saturating_mul(exponent.unsigned_abs()) <= MAX_BIG_BITS => self
+        .pow(exponent, context)
+        .map(Some)
+        .ok_or(EvalError::DivisionByZero),
+      Some(_) => Err(EvalError::Overflow),
+      None => Ok(None),
+    }
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Number for Complex {
+  fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Complex(z) => Some(*z),
+      _ => value.as_float().ok().map(Complex::from),
+    }
+  }
+
+  fn into_value(self) -> Value {
+    Value::Complex(self)
+  }
+
+  fn is_zero(&self) -> bool {
+    Complex::is_zero(*self)
+  }
+
+  fn add(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(*self + *other)
+  }
+
+  fn sub(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(*self - *other)
+  }
+
+  fn mul(&self, other: &Self, _: Context) -> Result<Self, EvalError> {
+    Ok(*self * *other)
+  }
+
+  fn div(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    Ok(Some(*self / *other))
+  }
+
+  /// Complex numbers have no ordering to floor by, so `//` and `%` are left
+  /// to the real fallback, which rejects them.
+  fn div_mod_floor(&self, _: &Self) -> Result<Option<(Self, Self)>, EvalError> {
+    Ok(None)
+  }
+
+  fn pow(&self, other: &Self, _: Context) -> Result<Option<Self>, EvalError> {
+    if Complex::is_zero(*self) && other.re < 0.0 {
+      return Err(EvalError::DivisionByZero);
+    }
+    if other.im == 0.0 && other.re.fract() == 0.0 && other.re.abs() <= i32::MAX as f64 {
+      return Ok(Some(self.powi(other.re as i32)));
+    }
+    Ok(Some(self.powc(*other)))
+  }
+
+  /// Complex numbers are only ever equal or unordered.
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    (self == other).then_some(Ordering::Equal)
+  }
+}
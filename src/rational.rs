@@ -0,0 +1,225 @@
+use std::{
+  cmp::Ordering,
+  fmt,
+  ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::bigint::BigInt;
+
+/// An exact fraction, kept in lowest terms with a positive denominator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rational {
+  numer: BigInt,
+  denom: BigInt,
+}
+
+impl Rational {
+  /// Returns `None` when `denom` is zero.
+  pub fn new(numer: BigInt, denom: BigInt) -> Option<Self> {
+    if denom.is_zero() {
+      return None;
+    }
+    let mut divisor = numer.gcd(&denom);
+    if denom.is_negative() {
+      divisor = -&divisor;
+    }
+    let (numer, _) = numer.div_rem(&divisor)?;
+    let (denom, _) = denom.div_rem(&divisor)?;
+    Some(Self { numer, denom })
+  }
+
+  pub fn numer(&self) -> &BigInt {
+    &self.numer
+  }
+
+  pub fn denom(&self) -> &BigInt {
+    &self.denom
+  }
+
+  pub fn is_integer(&self) -> bool {
+    self.denom == BigInt::from(1)
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.numer.is_zero()
+  }
+
+  pub fn abs(&self) -> Self {
+    Self {
+      numer: self.numer.abs(),
+      denom: self.denom.clone(),
+    }
+  }
+
+  /// The nearest float, computed from enough decimal digits of the quotient
+  /// that huge numerators and denominators don't overflow on the way.
+  pub fn to_f64(&self) -> f64 {
+    let digits = |n: &BigInt| n.abs().to_string().len() as u32;
+    let scale = 20 + digits(&self.denom).saturating_sub(digits(&self.numer));
+    let scaled = &self.numer * &BigInt::from(10).pow(scale);
+    let (quotient, _) = scaled
+      .div_rem(&self.denom)
+      .expect("denominator is never zero");
+    format!("{}e-{}", quotient, scale)
+      .parse()
+      .unwrap_or(f64::NAN)
+  }
+
+  /// Returns `None` when dividing by zero.
+  pub fn checked_div(&self, other: &Self) -> Option<Self> {
+    Self::new(&self.numer * &other.denom, &self.denom * &other.numer)
+  }
+
+  /// The largest integer not greater than `self`.
+  pub fn floor(&self) -> BigInt {
+    let (quotient, _) = self
+      .numer
+      .div_mod_floor(&self.denom)
+      .expect("denominator is never zero");
+    quotient
+  }
+
+  /// Raises to an integer power, or `None` for a negative power of zero.
+  pub fn pow(&self, exponent: i64) -> Option<Self> {
+    let power = u32::try_from(exponent.unsigned_abs()).ok()?;
+    let (numer, denom) = (self.numer.pow(power), self.denom.pow(power));
+    if exponent < 0 {
+      Self::new(denom, numer)
+    } else {
+      Self::new(numer, denom)
+    }
+  }
+}
+
+impl From<BigInt> for Rational {
+  fn from(n: BigInt) -> Self {
+    Self {
+      numer: n,
+      denom: BigInt::from(1),
+    }
+  }
+}
+
+impl Ord for Rational {
+  fn cmp(&self, other: &Self) -> Ordering {
+    (&self.numer * &other.denom).cmp(&(&other.numer * &self.denom))
+  }
+}
+
+impl PartialOrd for Rational {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Neg for &Rational {
+  type Output = Rational;
+
+  fn neg(self) -> Rational {
+    Rational {
+      numer: -&self.numer,
+      denom: self.denom.clone(),
+    }
+  }
+}
+
+impl Add for &Rational {
+  type Output = Rational;
+
+  fn add(self, other: &Rational) -> Rational {
+    let numer = &(&self.numer * &other.denom) + &(&other.numer * &self.denom);
+    Rational::new(numer, &self.denom * &other.denom).expect("denominators are never zero")
+  }
+}
+
+impl Sub for &Rational {
+  type Output = Rational;
+
+  fn sub(self, other: &Rational) -> Rational {
+    self + &-other
+  }
+}
+
+impl Mul for &Rational {
+  type Output = Rational;
+
+  fn mul(self, other: &Rational) -> Rational {
+    Rational::new(&self.numer * &other.numer, &self.denom * &other.denom)
+      .expect("denominators are never zero")
+  }
+}
+
+impl fmt::Display for Rational {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.is_integer() {
+      write!(f, "{}", self.numer)
+    } else {
+      write!(f, "{}/{}", self.numer, self.denom)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ratio(numer: i64, denom: i64) -> Rational {
+    Rational::new(BigInt::from(numer), BigInt::from(denom)).unwrap()
+  }
+
+  #[test]
+  fn new_reduces_to_lowest_terms_with_a_positive_denominator() {
+    for (numer, denom, expected) in [
+      (6, 4, "3/2"),
+      (6, -4, "-3/2"),
+      (-6, -4, "3/2"),
+      (-6, 4, "-3/2"),
+      (0, -5, "0"),
+      (10, 5, "2"),
+    ] {
+      let r = ratio(numer, denom);
+      assert_eq!(r.to_string(), expected, "{}/{}", numer, denom);
+      assert!(!r.denom().is_negative());
+    }
+    assert_eq!(Rational::new(BigInt::from(1), BigInt::from(0)), None);
+  }
+
+  #[test]
+  fn arithmetic_stays_in_lowest_terms() {
+    assert_eq!(&ratio(1, 2) + &ratio(1, 3), ratio(5, 6));
+    assert_eq!(&ratio(1, 6) + &ratio(1, 3), ratio(1, 2));
+    assert!((&ratio(1, 2) - &ratio(2, 4)).is_zero());
+    assert!((&ratio(2, 3) * &ratio(3, 2)).is_integer());
+    assert_eq!(ratio(1, 2).checked_div(&ratio(-3, 4)), Some(ratio(-2, 3)));
+    assert_eq!(ratio(1, 2).checked_div(&ratio(0, 1)), None);
+  }
+
+  #[test]
+  fn floor_rounds_towards_negative_infinity() {
+    for (numer, denom, floor) in [(7, 2, 3), (-7, 2, -4), (-4, 2, -2), (1, 3, 0), (-1, 3, -1)] {
+      assert_eq!(
+        ratio(numer, denom).floor(),
+        BigInt::from(floor),
+        "{}/{}",
+        numer,
+        denom
+      );
+    }
+  }
+
+  #[test]
+  fn pow_handles_negative_exponents() {
+    assert_eq!(ratio(2, 3).pow(2), Some(ratio(4, 9)));
+    assert_eq!(ratio(-2, 3).pow(-3), Some(ratio(-27, 8)));
+    assert_eq!(ratio(0, 1).pow(-1), None);
+  }
+
+  #[test]
+  fn to_f64_survives_huge_terms() {
+    assert_eq!(ratio(1, 10).to_f64(), 0.1);
+    assert_eq!(ratio(-1, 3).to_f64(), -1.0 / 3.0);
+    let huge = BigInt::from(10).pow(400);
+    let r = Rational::new(&huge + &BigInt::from(1), &huge * &BigInt::from(4)).unwrap();
+    assert_eq!(r.to_f64(), 0.25);
+  }
+}
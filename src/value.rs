@@ -0,0 +1,366 @@
+use std::{fmt, rc::Rc};
+
+use crate::{
+  bigint::BigInt,
+  builtins::{Arity, Builtin},
+  complex::Complex,
+  decimal::{Context, Decimal},
+  env::{Env, Function},
+  error::EvalError,
+  number::{self, MAX_BIG_BITS, Number},
+  rational::Rational,
+};
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone)]
+pub enum Value {
+  Int(i64),
+  /// An integer in bignum mode, where arithmetic never overflows.
+  Big(BigInt),
+  /// An exact fraction in rational mode.
+  Ratio(Rational),
+  /// An exact decimal in decimal mode.
+  Decimal(Decimal),
+  Float(f64),
+  /// A complex number, e.g. from the literal `3 + 4i`.
+  Complex(Complex),
+  Bool(bool),
+  List(Vec<Value>),
+  Func(Rc<Function>),
+  /// A built-in function referenced by name, e.g. `sqrt` in `map(sqrt, xs)`.
+  Builtin(String),
+}
+
+impl Value {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::Int(_) | Value::Big(_) => "integer",
+      Value::Ratio(_) => "rational",
+      Value::Decimal(_) => "decimal",
+      Value::Complex(_) => "complex",
+      Value::Float(_) => "float",
+      Value::Bool(_) => "boolean",
+      Value::List(_) => "list",
+      Value::Func(_) | Value::Builtin(_) => "function",
+    }
+  }
+
+  pub fn as_float(&self) -> Result<f64, EvalError> {
+    match self {
+      Value::Int(n) => Ok(*n as f64),
+      Value::Big(n) => Ok(n.to_f64()),
+      Value::Ratio(r) => Ok(r.to_f64()),
+      Value::Decimal(d) => Ok(d.to_f64()),
+      Value::Complex(_) => Err(self.mismatch("real number")),
+      Value::Float(x) => Ok(*x),
+      _ => Err(self.mismatch("number")),
+    }
+  }
+
+  pub fn as_bool(&self) -> Result<bool, EvalError> {
+    match self {
+      Value::Bool(b) => Ok(*b),
+      _ => Err(self.mismatch("boolean")),
+    }
+  }
+
+  pub fn as_list(&self) -> Result<&[Value], EvalError> {
+    match self {
+      Value::List(items) => Ok(items),
+      _ => Err(self.mismatch("list")),
+    }
+  }
+
+  fn mismatch(&self, expected: &'static str) -> EvalError {
+    EvalError::TypeMismatch {
+      expected,
+      found: self.type_name(),
+    }
+  }
+
+  /// Which representation arithmetic on this value needs, or `None` for
+  /// non-numbers.
+  fn kind(&self) -> Option<Kind> {
+    match self {
+      Value::Int(_) => Some(Kind::Int),
+      Value::Big(_) => Some(Kind::Big),
+      Value::Ratio(_) => Some(Kind::Ratio),
+      Value::Decimal(_) => Some(Kind::Decimal),
+      Value::Float(_) => Some(Kind::Float),
+      Value::Complex(_) => Some(Kind::Complex),
+      _ => None,
+    }
+  }
+
+  /// Applies a binary arithmetic operator in the wider representation of
+  /// the two operands, so an integer and a fraction give a fraction. Where
+  /// that representation has no exact result, e.g. `1 / 2` for integers,
+  /// the result is computed as a float. Decimals are rounded as set by
+  /// `context`.
+  pub fn binary(
+    op: &'static str,
+    lhs: &Value,
+    rhs: &Value,
+    context: Context,
+  ) -> Result<Value, EvalError> {
+    if matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=") {
+      return Value::compare(op, lhs, rhs);
+    }
+
+    let result = match lhs.kind().max(rhs.kind()) {
+      Some(Kind::Int) => apply_as::<i64>(op, lhs, rhs, context)?,
+      Some(Kind::Big) => apply_as::<BigInt>(op, lhs, rhs, context)?,
+      Some(Kind::Ratio) => apply_as::<Rational>(op, lhs, rhs, context)?,
+      Some(Kind::Decimal) => apply_as::<Decimal>(op, lhs, rhs, context)?,
+      Some(Kind::Complex) => apply_as::<Complex>(op, lhs, rhs, context)?,
+      Some(Kind::Float) | None => None,
+    };
+    if let Some(result) = result {
+      return Ok(result);
+    }
+
+    let (a, b) = (lhs.as_float()?, rhs.as_float()?);
+    number::apply(op, &a, &b, context)?
+      .map(Value::Float)
+      .ok_or(EvalError::UnknownOperator(op))
+  }
+
+  /// Applies a comparison operator, giving a boolean. Numbers compare by
+  /// value whatever their representation, and booleans can be tested for
+  /// equality.
+  fn compare(op: &'static str, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    if let (Value::Bool(a), Value::Bool(b)) = (lhs, rhs) {
+      return match op {
+        "==" => Ok(Value::Bool(a == b)),
+        "!=" => Ok(Value::Bool(a != b)),
+        _ => Err(lhs.mismatch("number")),
+      };
+    }
+
+    let result = match lhs.kind().max(rhs.kind()) {
+      Some(Kind::Int) => compare_as::<i64>(op, lhs, rhs),
+      Some(Kind::Big) => compare_as::<BigInt>(op, lhs, rhs),
+      Some(Kind::Ratio) => compare_as::<Rational>(op, lhs, rhs),
+      Some(Kind::Decimal) => compare_as::<Decimal>(op, lhs, rhs),
+      Some(Kind::Complex) if matches!(op, "==" | "!=") => compare_as::<Complex>(op, lhs, rhs),
+      _ => None,
+    };
+    if let Some(result) = result {
+      return Ok(Value::Bool(result));
+    }
+
+    let (a, b) = (lhs.as_float()?, rhs.as_float()?);
+    number::compare(op, &a, &b)
+      .map(Value::Bool)
+      .ok_or(EvalError::UnknownOperator(op))
+  }
+
+  /// Applies a prefix or postfix operator.
+  pub fn unary(op: &'static str, value: &Value) -> Result<Value, EvalError> {
+    match (op, value) {
+      ("-", Value::Int(n)) => n.checked_neg().map(Value::Int).ok_or(EvalError::Overflow),
+      ("-", Value::Big(n)) => Ok(Value::Big(-n)),
+      ("-", Value::Ratio(r)) => Ok(Value::Ratio(-r)),
+      ("-", Value::Decimal(d)) => Ok(Value::Decimal(-d)),
+      ("-", Value::Complex(z)) => Ok(Value::Complex(-*z)),
+      ("-", _) => Ok(Value::Float(-value.as_float()?)),
+      ("+", Value::Complex(_)) => Ok(value.clone()),
+      ("+", _) => value.as_float().map(|_| value.clone()),
+      ("!", _) => factorial(value),
+      ("not", _) => value.as_bool().map(|b| Value::Bool(!b)),
+      ("%", Value::Ratio(r)) => Ok(Value::Ratio(
+        r.checked_div(&Rational::from(BigInt::from(100)))
+          .expect("100 is not zero"),
+      )),
+      ("%", Value::Decimal(d)) => Ok(Value::Decimal(d.percent())),
+      ("%", _) => Ok(Value::Float(value.as_float()? / 100.0)),
+      _ => Err(EvalError::UnknownOperator(op)),
+    }
+  }
+
+  /// Calls a function value. Errors carry no span since they may come from
+  /// a body defined on another line; the caller reports them at the call.
+  pub fn call(&self, args: Vec<Value>, env: &mut Env) -> Result<Value, EvalError> {
+    match self {
+      Value::Func(function) => {
+        if function.params.len() != args.len() {
+          return Err(EvalError::WrongArity {
+            name: function.name.clone(),
+            expected: Arity::Exact(function.params.len()),
+            found: args.len(),
+          });
+        }
+
+        let mut locals = function.captured.clone();
+        locals.extend(function.params.iter().cloned().zip(args));
+
+        env.enter(locals)?;
+        let result = function.body.eval(env);
+        env.leave();
+
+        result.map_err(|err| err.error)
+      }
+      Value::Builtin(name) => {
+        let builtin = Builtin::lookup(name).expect("builtin values are created from known names");
+        if !builtin.arity().accepts(args.len()) {
+          return Err(EvalError::WrongArity {
+            name: name.clone(),
+            expected: builtin.arity(),
+            found: args.len(),
+          });
+        }
+        builtin.call(args, env)
+      }
+      _ => Err(self.mismatch("function")),
+    }
+  }
+}
+
+/// Numeric representations, ordered so that mixing two uses the later one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+  Int,
+  Big,
+  Ratio,
+  Decimal,
+  Float,
+  Complex,
+}
+
+/// Applies `op` in the representation `N`, or returns `None` when an
+/// operand or the result has no exact representation there.
+fn apply_as<N: Number>(
+  op: &str,
+  lhs: &Value,
+  rhs: &Value,
+  context: Context,
+) -> Result<Option<Value>, EvalError> {
+  match (N::from_value(lhs), N::from_value(rhs)) {
+    (Some(a), Some(b)) => Ok(number::apply(op, &a, &b, context)?.map(N::into_value)),
+    _ => Ok(None),
+  }
+}
+
+/// Like [`apply_as`] but for comparisons.
+fn compare_as<N: Number>(op: &str, lhs: &Value, rhs: &Value) -> Option<bool> {
+  match (N::from_value(lhs), N::from_value(rhs)) {
+    (Some(a), Some(b)) => number::compare(op, &a, &b),
+    _ => None,
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Int(n) => write!(f, "{}", n),
+      Value::Big(n) => write!(f, "{}", n),
+      Value::Ratio(r) => write!(f, "{}", r),
+      Value::Decimal(d) => write!(f, "{}", d),
+      Value::Float(x) => fmt_float(*x, f),
+      Value::Complex(z) => fmt_complex(*z, f),
+      Value::Bool(b) => write!(f, "{}", b),
+      Value::List(items) => {
+        write!(f, "[")?;
+        for (i, item) in items.iter().enumerate() {
+          if i > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+      }
+      Value::Func(function) => {
+        write!(
+          f,
+          "<function {}({})>",
+          function.name,
+          function.params.join(", ")
+        )
+      }
+      Value::Builtin(name) => write!(f, "<builtin {}>", name),
+    }
+  }
+}
+
+fn factorial(value: &Value) -> Result<Value, EvalError> {
+  let n = value.as_float()?;
+  if n < 0.0 || n.fract() != 0.0 {
+    return Err(EvalError::InvalidOperand { op: "!", value: n });
+  }
+  if let Value::Int(n) = value {
+    return (1..=*n)
+      .try_fold(1i64, i64::checked_mul)
+      .map(Value::Int)
+      .ok_or(EvalError::Overflow);
+  }
+  if let Value::Big(n) = value {
+    return big_factorial(n).map(Value::Big);
+  }
+  // The float check above misses fractions too small for an `f64`.
+  if let Value::Ratio(r) = value {
+    if !r.is_integer() {
+      return Err(EvalError::InvalidOperand { op: "!", value: n });
+    }
+    return big_factorial(r.numer()).map(|n| Value::Ratio(Rational::from(n)));
+  }
+  if let Value::Decimal(d) = value {
+    let n = d
+      .to_integer()
+      .ok_or(EvalError::InvalidOperand { op: "!", value: n })?;
+    return big_factorial(&n).map(|n| Value::Decimal(Decimal::from(n)));
+  }
+
+  // 171! already overflows f64, so skip the loop for large inputs.
+  if n > 170.0 {
+    return Ok(Value::Float(f64::INFINITY));
+  }
+  Ok(Value::Float((1..=n as u64).map(|i| i as f64).product()))
+}
+
+fn big_factorial(n: &BigInt) -> Result<BigInt, EvalError> {
+  let n = n.to_i64().ok_or(EvalError::Overflow)?;
+  let mut result = BigInt::from(1);
+  for i in 2..=n {
+    result = &result * &BigInt::from(i);
+    if result.bits() > MAX_BIG_BITS {
+      return Err(EvalError::Overflow);
+    }
+  }
+  Ok(result)
+}
+
+/// Formats as `a + bi`, without the `.0` that integral floats otherwise get
+/// since the `i` already sets the result apart from an integer.
+fn fmt_complex(z: Complex, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  let part = |x: f64| {
+    let formatted = Value::Float(x).to_string();
+    match formatted.strip_suffix(".0") {
+      Some(integral) => integral.to_string(),
+      None => formatted,
+    }
+  };
+
+  if z.re == 0.0 {
+    write!(f, "{}i", part(z.im))
+  } else {
+    let sign = if z.im.is_sign_negative() { "-" } else { "+" };
+    write!(f, "{} {} {}i", part(z.re), sign, part(z.im.abs()))
+  }
+}
+
+/// Formats floats so they can't be mistaken for integers, switching to
+/// scientific notation for very large and very small magnitudes.
+fn fmt_float(x: f64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  let magnitude = x.abs();
+  if x.is_nan() {
+    write!(f, "nan")
+  } else if x.is_infinite() {
+    write!(f, "{}inf", if x < 0.0 { "-" } else { "" })
+  } else if magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+    write!(f, "{:e}", x)
+  } else if x.fract() == 0.0 {
+    write!(f, "{:.1}", x)
+  } else {
+    write!(f, "{}", x)
+  }
+}